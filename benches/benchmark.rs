@@ -1,8 +1,14 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use fuzzysearchrs::{
-    fuzzy_search_options::FuzzySearchOptions,
+    fuzzy_search_options::{Engine, FuzzySearchOptions, Prefilter},
     fuzzy_search_substitutions_only::FuzzySearchSubstitutionsOnly,
-    fuzzy_search_substitutions_only_simd::FuzzySearchSubstitutionsOnlySimdTest, FuzzySearch,
+    fuzzy_search_substitutions_only_bytes::FuzzySearchSubstitutionsOnlyBytes,
+    fuzzy_search_substitutions_only_multi::FuzzySearchSubstitutionsOnlyMulti,
+    fuzzy_search_substitutions_only_simd::FuzzySearchSubstitutionsOnlySimdTest,
+    multi_pattern_search::MultiPatternSearch,
+    pattern::Pattern,
+    simd_level::SimdLevel,
+    FuzzySearch,
 };
 use test_data::{get_cia_text, get_ecoli_text, MEDIUM_PATTERN, MEDIUM_TEXT};
 
@@ -49,10 +55,11 @@ fn bench_standard(c: &mut Criterion) {
         })
     });
 
-    c.bench_function("ecoli", |b| {
-        let text = get_ecoli_text();
-        let pattern = "cccctgaccatcaaccagcggataacggtaagagaacg";
-        let options = FuzzySearchOptions::new(distance);
+    c.bench_function("cia_exact_seeds", |b| {
+        let text = get_cia_text();
+        let pattern = "conftitufional";
+        let mut options = FuzzySearchOptions::new(distance);
+        options.prefilter = Prefilter::ExactSeeds;
 
         b.iter(|| {
             let _ = FuzzySearch::find(pattern, &text.chars().collect::<Vec<_>>(), &options)
@@ -60,25 +67,60 @@ fn bench_standard(c: &mut Criterion) {
         })
     });
 
-    c.bench_function("cia_substitutions_only", |b| {
+    c.bench_function("cia_qgrams", |b| {
         let text = get_cia_text();
-        let pattern = "conftitufional"; // well, since the text probably doesnt contain bad ocr, lets introduce some errors here instead...
+        let pattern = "conftitufional";
+        let mut options = FuzzySearchOptions::new(distance);
+        options.prefilter = Prefilter::QGrams { q: None };
 
         b.iter(|| {
-            let _ =
-                FuzzySearchSubstitutionsOnly::find(pattern, &text, distance).collect::<Vec<_>>();
+            let _ = FuzzySearch::find(pattern, &text.chars().collect::<Vec<_>>(), &options)
+                .collect::<Vec<_>>();
+        })
+    });
+
+    c.bench_function("cia_simd", |b| {
+        let text = get_cia_text();
+        let pattern = "conftitufional";
+        let mut options = FuzzySearchOptions::new(distance);
+        options.engine = Engine::Simd;
+
+        b.iter(|| {
+            let _ = FuzzySearch::find(pattern, &text.chars().collect::<Vec<_>>(), &options)
+                .collect::<Vec<_>>();
         })
     });
 
-    c.bench_function("ecoli_substitutions_only", |b| {
-        let text = get_ecoli_text();
-        let pattern = "cccctgaccatcaaccagcGGataacggtaagagaacg";
+    c.bench_function("cia_substitutions_only", |b| {
+        let text = get_cia_text();
+        let pattern = "conftitufional"; // well, since the text probably doesnt contain bad ocr, lets introduce some errors here instead...
 
         b.iter(|| {
             let _ =
                 FuzzySearchSubstitutionsOnly::find(pattern, &text, distance).collect::<Vec<_>>();
         })
     });
+
+    if let Some(text) = get_ecoli_text() {
+        c.bench_function("ecoli", |b| {
+            let pattern = "cccctgaccatcaaccagcggataacggtaagagaacg";
+            let options = FuzzySearchOptions::new(distance);
+
+            b.iter(|| {
+                let _ = FuzzySearch::find(pattern, &text.chars().collect::<Vec<_>>(), &options)
+                    .collect::<Vec<_>>();
+            })
+        });
+
+        c.bench_function("ecoli_substitutions_only", |b| {
+            let pattern = "cccctgaccatcaaccagcGGataacggtaagagaacg";
+
+            b.iter(|| {
+                let _ = FuzzySearchSubstitutionsOnly::find(pattern, &text, distance)
+                    .collect::<Vec<_>>();
+            })
+        });
+    }
 }
 
 fn bench_simd_substitutions_only_cia(c: &mut Criterion) {
@@ -102,13 +144,23 @@ fn bench_simd_substitutions_only_cia(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            let _ = FuzzySearchSubstitutionsOnlyBytes::find(pattern, &text, max_distance)
+                .collect::<Vec<_>>();
+        });
+    });
+
     group.finish();
 }
 
 fn bench_simd_substitutions_only_ecoli(c: &mut Criterion) {
+    let Some(text) = get_ecoli_text() else {
+        return;
+    };
+
     let mut group = c.benchmark_group("simd_substitutions_only_ecoli");
 
-    let text = get_ecoli_text();
     let pattern = "cccctgaccatcaaccagcGGataacggtaagagaacg";
     let max_distance = 3;
 
@@ -126,13 +178,191 @@ fn bench_simd_substitutions_only_ecoli(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            let _ = FuzzySearchSubstitutionsOnlyBytes::find(pattern, &text, max_distance)
+                .collect::<Vec<_>>();
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_simd_levels_cia(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simd_levels_cia");
+
+    let text = get_cia_text();
+    let pattern = "conftitufional";
+    let max_distance = 3;
+
+    let levels = [
+        SimdLevel::Scalar,
+        SimdLevel::Portable,
+        SimdLevel::Sse42,
+        SimdLevel::Avx2,
+        SimdLevel::Avx512,
+    ];
+
+    for level in levels.into_iter().filter(|l| l.is_supported()) {
+        group.bench_function(format!("{level:?}"), |b| {
+            b.iter(|| {
+                let _ = FuzzySearchSubstitutionsOnlySimdTest::find(pattern, &text, max_distance)
+                    .with_simd_level(level)
+                    .collect::<Vec<_>>();
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_simd_levenshtein_cia(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simd_levenshtein_cia");
+
+    let text = get_cia_text().chars().collect::<Vec<_>>();
+    let pattern = "conftitufional";
+
+    for max_distance in [3, 5] {
+        for engine in [Engine::CandidateStack, Engine::Simd] {
+            let mut options = FuzzySearchOptions::new(max_distance);
+            options.engine = engine;
+
+            group.bench_function(format!("{engine:?}_{max_distance}"), |b| {
+                b.iter(|| {
+                    let _ = FuzzySearch::find(pattern, &text, &options).collect::<Vec<_>>();
+                });
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_multi_pattern_cia(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_pattern_cia");
+
+    let text = get_cia_text().chars().collect::<Vec<_>>();
+    let patterns = [
+        "conftitufional",
+        "goverment",
+        "populatoin",
+        "agricultrue",
+        "independance",
+        "parliamant",
+        "internatoinal",
+        "petroleun",
+    ];
+    let options = FuzzySearchOptions::new(2);
+
+    group.bench_function("separate", |b| {
+        b.iter(|| {
+            for pattern in patterns {
+                let _ = FuzzySearch::find(pattern, &text, &options).collect::<Vec<_>>();
+            }
+        });
+    });
+
+    group.bench_function("single_pass", |b| {
+        let mut search = MultiPatternSearch::new();
+        for pattern in patterns {
+            search.add(Pattern::literal(pattern), FuzzySearchOptions::new(2));
+        }
+
+        b.iter(|| {
+            let _ = search.find(&text);
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_parallel_cia(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel_cia");
+
+    let text = get_cia_text().chars().collect::<Vec<_>>();
+    let pattern = Pattern::literal("conftitufional");
+    let options = FuzzySearchOptions::new(2);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let _ = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+        });
+    });
+
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            let _ = FuzzySearch::find_parallel(&pattern, &text, &options, threads);
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_barcodes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("barcodes");
+
+    // 48 codes of 8 bases, taken from a periodic read so some of them occur
+    let bases = "ACGTTGCAAGCTTCGACATGGTACCAGTTGACCTAGGATCCGATATCGCG".repeat(40);
+    let barcodes = (0..48).map(|i| &bases[i..i + 8]).collect::<Vec<_>>();
+    let read = bases.repeat(5);
+
+    group.bench_function("separate", |b| {
+        b.iter(|| {
+            for barcode in &barcodes {
+                let _ = FuzzySearchSubstitutionsOnly::find(barcode, &read, 1).collect::<Vec<_>>();
+            }
+        });
+    });
+
+    group.bench_function("multi", |b| {
+        b.iter(|| {
+            let _ =
+                FuzzySearchSubstitutionsOnlyMulti::find(&barcodes, &read, 1).collect::<Vec<_>>();
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_levenshtein_repetitive(c: &mut Criterion) {
+    let mut group = c.benchmark_group("levenshtein_repetitive");
+
+    // longer patterns with more edits reach the same candidate states along many paths
+    let cia = get_cia_text().chars().collect::<Vec<_>>();
+    let options = FuzzySearchOptions::new(4);
+
+    group.bench_function("cia", |b| {
+        b.iter(|| {
+            let _ = FuzzySearch::find("the government of the", &cia, &options).collect::<Vec<_>>();
+        });
+    });
+
+    if let Some(ecoli) = get_ecoli_text() {
+        let ecoli = ecoli.chars().collect::<Vec<_>>();
+
+        group.bench_function("ecoli", |b| {
+            b.iter(|| {
+                let _ =
+                    FuzzySearch::find("cccctgaccatcaaccagcggataacggtaagagaacg", &ecoli, &options)
+                        .collect::<Vec<_>>();
+            });
+        });
+    }
+
     group.finish();
 }
 
 criterion_group!(
     benches,
     bench_standard,
+    bench_levenshtein_repetitive,
     bench_simd_substitutions_only_cia,
+    bench_simd_levels_cia,
+    bench_simd_levenshtein_cia,
+    bench_multi_pattern_cia,
+    bench_parallel_cia,
+    bench_barcodes,
     bench_simd_substitutions_only_ecoli
 );
 criterion_main!(benches);
@@ -0,0 +1,8 @@
+#!/bin/sh
+# Downloads the E. coli genome from the Canterbury large corpus into benches/test_files
+set -e
+
+cd "$(dirname "$0")/test_files"
+curl -sSfL -o large.zip https://corpus.canterbury.ac.nz/resources/large.zip
+unzip -o large.zip E.coli
+rm large.zip
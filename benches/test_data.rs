@@ -1,4 +1,4 @@
-use std::{fs::File, io::Read};
+use std::{fs::File, io::Read, path::Path};
 
 pub const MEDIUM_PATTERN: &str = "fooo--foo-----fo";
 pub const MEDIUM_TEXT: &str = "foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--foo-----fo--foo-f--fooo--";
@@ -7,8 +7,17 @@ pub fn get_cia_text() -> String {
     get_text("benches/test_files/world192.txt")
 }
 
-pub fn get_ecoli_text() -> String {
-    get_text("benches/test_files/E.coli")
+/// The E. coli genome from the Canterbury large corpus is not checked in, `benches/fetch_test_files.sh` downloads it.
+/// None if it has not been fetched, so the E. coli benches are skipped
+pub fn get_ecoli_text() -> Option<String> {
+    let path = "benches/test_files/E.coli";
+
+    if Path::new(path).exists() {
+        Some(get_text(path))
+    } else {
+        eprintln!("{path} not found, run benches/fetch_test_files.sh to bench on it");
+        None
+    }
 }
 
 fn get_text(path: &str) -> String {
@@ -1,3 +1,46 @@
+/// Fixed point cost of a single edit, edits at low confidence text positions cost less
+pub const EDIT_COST: u32 = 1000;
+
+/// Weighted costs of the edits in a candidate, in units of [`EDIT_COST`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Costs {
+    pub total: u32,
+    pub substitutions: u32,
+    pub deletions: u32,
+    pub insertions: u32,
+}
+
+impl Costs {
+    pub fn substitute(self, cost: u32) -> Self {
+        Costs {
+            total: self.total + cost,
+            substitutions: self.substitutions + cost,
+            ..self
+        }
+    }
+
+    pub fn delete(self, cost: u32) -> Self {
+        Costs {
+            total: self.total + cost,
+            deletions: self.deletions + cost,
+            ..self
+        }
+    }
+
+    pub fn insert(self, cost: u32) -> Self {
+        Costs {
+            total: self.total + cost,
+            insertions: self.insertions + cost,
+            ..self
+        }
+    }
+
+    /// Total cost as a distance, equal to the distance if all edits have full cost
+    pub fn weighted_distance(&self) -> f32 {
+        self.total as f32 / EDIT_COST as f32
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CandidateMatch {
     pub start_index: usize,
@@ -7,6 +50,9 @@ pub struct CandidateMatch {
     pub deletions: usize,
     pub substitutions: usize,
     pub insertions: usize,
+    pub ignored: usize,
+    pub costs: Costs,
+    pub edit_history: u64, // one bit per pattern step, set if the step was an edit, most recent step in the lowest bit
 }
 
 impl CandidateMatch {
@@ -19,6 +65,45 @@ impl CandidateMatch {
             deletions: 0,
             substitutions: 0,
             insertions: 0,
+            ignored: 0,
+            costs: Costs::default(),
+            edit_history: 0,
         }
     }
+    /// Complete match found by an engine which computes whole alignments, all edits have full cost
+    pub fn from_alignment(
+        start_index: usize,
+        end_index: usize,
+        pattern_length: usize,
+        alignment: Alignment,
+    ) -> Self {
+        let costs = Costs::default()
+            .substitute(alignment.substitutions as u32 * EDIT_COST)
+            .delete(alignment.deletions as u32 * EDIT_COST)
+            .insert(alignment.insertions as u32 * EDIT_COST);
+
+        CandidateMatch {
+            pattern_index: pattern_length,
+            distance: alignment.distance(),
+            deletions: alignment.deletions,
+            substitutions: alignment.substitutions,
+            insertions: alignment.insertions,
+            costs,
+            ..Self::new(start_index, end_index)
+        }
+    }
+}
+
+/// Edit counts of an alignment between the pattern and a text range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Alignment {
+    pub substitutions: usize,
+    pub deletions: usize,
+    pub insertions: usize,
+}
+
+impl Alignment {
+    pub fn distance(&self) -> usize {
+        self.substitutions + self.deletions + self.insertions
+    }
 }
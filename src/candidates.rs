@@ -0,0 +1,366 @@
+use std::{borrow::Cow, ops::Range};
+
+use crate::{
+    candidate_match::{CandidateMatch, EDIT_COST},
+    fuzzy_search_banded::FuzzySearchBanded,
+    fuzzy_search_bitap::{self, FuzzySearchBitap, FuzzySearchBitapMatches},
+    fuzzy_search_levenshtein::FuzzySearchLevenshtein,
+    fuzzy_search_levenshtein_simd::FuzzySearchLevenshteinSimd,
+    fuzzy_search_myers::FuzzySearchMyers,
+    fuzzy_search_options::{Engine, FuzzySearchOptions, UnsupportedEngine},
+    pattern::{Pattern, PatternElement},
+    prefilter::{self, Windows},
+};
+
+/// Candidate matches from the engine selected in the options
+pub(crate) enum Candidates<'a> {
+    CandidateStack(FuzzySearchLevenshtein<'a>),
+    Banded(FuzzySearchBanded<'a>),
+    BitapSubstitutions(FuzzySearchBitapMatches<'a>),
+    EmptyText(Option<CandidateMatch>),
+}
+
+// above this the per error level work of bitap exceeds the single pass of myers
+const AUTO_BITAP_MAX_DISTANCE: usize = 8;
+
+impl<'a> Candidates<'a> {
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        edit_costs: Option<Cow<'a, [u32]>>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        let start_windows = prefilter::start_windows(
+            pattern.prepare(options).elements(),
+            &text_chars,
+            edit_costs.is_some(),
+            options,
+        );
+
+        Self::find_in_windows(pattern, text_chars, edit_costs, options, start_windows)
+    }
+
+    /// Same as find, but only matches starting in the windows are searched for instead of those from the prefilter in the options
+    pub fn find_in_windows(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        edit_costs: Option<Cow<'a, [u32]>>,
+        options: &'a FuzzySearchOptions,
+        start_windows: Option<Windows>,
+    ) -> Self {
+        // every engine finds the same matches, so the candidate stack can stand in for one which does not support the options
+        let engine =
+            Self::engine(pattern, edit_costs.is_some(), options).unwrap_or(Engine::CandidateStack);
+
+        // the scanning engines need the text up to the end of the longest match starting in a window
+        let scan_windows = start_windows
+            .as_ref()
+            .map(|windows| windows.extended(longest_match(pattern, options)));
+
+        Self::with_engine(
+            engine,
+            pattern,
+            text_chars,
+            edit_costs,
+            options,
+            start_windows,
+            scan_windows,
+        )
+    }
+
+    /// Continues after an exact match starting at the index before the start windows,
+    /// like the candidate stack does within its windows
+    pub fn after_exact_match(self, start_index: usize) -> Self {
+        match self {
+            Candidates::CandidateStack(candidates) => {
+                Candidates::CandidateStack(candidates.after_exact_match(start_index))
+            }
+            Candidates::Banded(candidates) => {
+                Candidates::Banded(candidates.after_exact_match(start_index))
+            }
+            Candidates::BitapSubstitutions(candidates) => {
+                Candidates::BitapSubstitutions(candidates.after_exact_match(start_index))
+            }
+            candidates => candidates,
+        }
+    }
+
+    /// The engine selected in the options, with auto resolved
+    pub fn engine(
+        pattern: &Pattern,
+        weighted: bool,
+        options: &FuzzySearchOptions,
+    ) -> Result<Engine, UnsupportedEngine> {
+        let engine = match options.engine {
+            Engine::Auto => auto_engine(pattern, weighted, options),
+            engine => engine,
+        };
+
+        match unsupported_feature(engine, pattern, weighted, options) {
+            Some(feature) if engine != Engine::CandidateStack => {
+                Err(UnsupportedEngine { engine, feature })
+            }
+            _ => Ok(engine),
+        }
+    }
+
+    /// Candidates from the engine, the candidate stack only starts matches in the start windows
+    /// and the scanning engines only read the text in the scan windows.
+    /// The scanning engines find where matches end, then the banded dp traces the starts before those ends back,
+    /// except for substitutions only bitap which knows the starts. So the candidates are the same for every engine
+    pub fn with_engine(
+        engine: Engine,
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        edit_costs: Option<Cow<'a, [u32]>>,
+        options: &'a FuzzySearchOptions,
+        start_windows: Option<Windows>,
+        scan_windows: Option<Windows>,
+    ) -> Self {
+        // the engines only start matches at text chars, but anchored matches of the empty text are the deleted pattern
+        if text_chars.is_empty() && (options.is_anchored_start() || options.is_anchored_end()) {
+            return Candidates::EmptyText(empty_text_match(pattern, options));
+        }
+
+        let substitutions_only = options.max_deletions == 0 && options.max_insertions == 0;
+
+        let windows = match engine {
+            Engine::CandidateStack => {
+                return Candidates::CandidateStack(
+                    FuzzySearchLevenshtein::find(pattern, text_chars, edit_costs, options)
+                        .with_windows(start_windows),
+                )
+            }
+            Engine::Banded => start_windows,
+            // every match has the pattern length, so bitap knows the starts already
+            Engine::Bitap if substitutions_only => {
+                return Candidates::BitapSubstitutions(
+                    FuzzySearchBitap::find(pattern, text_chars, options)
+                        .with_windows(scan_windows)
+                        .substitution_matches(start_windows),
+                )
+            }
+            Engine::Myers => end_windows(
+                FuzzySearchMyers::find(pattern, Cow::Borrowed(&text_chars), options)
+                    .with_windows(scan_windows),
+                pattern,
+                options,
+                start_windows,
+            ),
+            Engine::Bitap => end_windows(
+                FuzzySearchBitap::find(pattern, Cow::Borrowed(&text_chars), options)
+                    .with_windows(scan_windows),
+                pattern,
+                options,
+                start_windows,
+            ),
+            Engine::Simd => end_windows(
+                FuzzySearchLevenshteinSimd::find(pattern, Cow::Borrowed(&text_chars), options)
+                    .with_windows(scan_windows),
+                pattern,
+                options,
+                start_windows,
+            ),
+            Engine::Auto => unreachable!("auto engine is resolved before"),
+        };
+
+        Candidates::Banded(
+            FuzzySearchBanded::find(pattern, text_chars, options).with_windows(windows),
+        )
+    }
+}
+
+/// Picks the candidate stack if only it supports the options, and otherwise bitap for short patterns and small distances or myers
+fn auto_engine(pattern: &Pattern, weighted: bool, options: &FuzzySearchOptions) -> Engine {
+    let substitutions_only = options.max_deletions == 0 && options.max_insertions == 0;
+
+    if unsupported_feature(Engine::Myers, pattern, weighted, options).is_some() {
+        Engine::CandidateStack
+    } else if pattern.prepare(options).len() <= fuzzy_search_bitap::MAX_PATTERN_LENGTH
+        && (substitutions_only || options.max_total_distance <= AUTO_BITAP_MAX_DISTANCE)
+    {
+        Engine::Bitap
+    } else {
+        Engine::Myers
+    }
+}
+
+/// Features which only the candidate stack engine supports, since they dont fit a plain edit distance matrix
+fn unsupported_feature(
+    engine: Engine,
+    pattern: &Pattern,
+    weighted: bool,
+    options: &FuzzySearchOptions,
+) -> Option<&'static str> {
+    if engine == Engine::Bitap
+        && pattern.prepare(options).len() > fuzzy_search_bitap::MAX_PATTERN_LENGTH
+    {
+        Some("a pattern longer than 64 chars")
+    } else if pattern.has_gaps() {
+        Some("a pattern with gaps")
+    } else if weighted {
+        Some("weighted search")
+    } else if options.layout_tolerant {
+        Some("layout_tolerant")
+    } else if options.ignorable_chars.is_some() {
+        Some("ignorable_chars")
+    } else if options.edit_density.is_some() {
+        Some("edit_density")
+    } else {
+        None
+    }
+}
+
+/// Longest text range a match can span
+fn longest_match(pattern: &Pattern, options: &FuzzySearchOptions) -> usize {
+    pattern.prepare(options).len() + options.max_insertions.min(options.max_total_distance)
+}
+
+/// Windows of the starts of matches ending at the ends found by a scanning engine, inside the start windows if given
+fn end_windows(
+    ends: impl Iterator<Item = usize>,
+    pattern: &Pattern,
+    options: &FuzzySearchOptions,
+    start_windows: Option<Windows>,
+) -> Option<Windows> {
+    // if the whole pattern can be deleted a match can start anywhere, and end before the first scanned char
+    if pattern.prepare(options).len() <= options.max_deletions.min(options.max_total_distance) {
+        return start_windows;
+    }
+
+    let longest_match = longest_match(pattern, options);
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for end_index in ends {
+        let range = end_index.saturating_sub(longest_match)..end_index + 1;
+
+        match ranges.last_mut() {
+            Some(last) if range.start <= last.end => last.end = range.end,
+            _ => ranges.push(range),
+        }
+    }
+
+    let windows = Windows::new(ranges);
+
+    Some(match start_windows {
+        Some(start_windows) => windows.intersected(&start_windows),
+        None => windows,
+    })
+}
+
+/// Match of the pattern in an empty text, where every pattern element is deleted and gaps are empty
+fn empty_text_match(pattern: &Pattern, options: &FuzzySearchOptions) -> Option<CandidateMatch> {
+    let prepared = pattern.prepare(options);
+    let mut candidate = CandidateMatch::new(0, 0);
+
+    for element in prepared.elements() {
+        candidate = match element {
+            PatternElement::Gap { min: 0, .. } => CandidateMatch {
+                edit_history: candidate.edit_history << 1,
+                ..candidate
+            },
+            PatternElement::Gap { .. } => return None,
+            _ => CandidateMatch {
+                deletions: candidate.deletions + 1,
+                distance: candidate.distance + 1,
+                costs: candidate.costs.delete(EDIT_COST),
+                edit_history: candidate.edit_history << 1 | 1,
+                ..candidate
+            },
+        };
+
+        if !options.allows_edit_history(candidate.edit_history) {
+            return None;
+        }
+    }
+
+    let allowed = !prepared.is_empty()
+        && candidate.costs.total <= options.max_total_cost()
+        && candidate.deletions <= options.max_deletions
+        && options.allows_match_start(&[], 0)
+        && options.allows_match_end(&[], 0);
+
+    allowed.then_some(CandidateMatch {
+        pattern_index: prepared.len(),
+        ..candidate
+    })
+}
+
+impl<'a> Iterator for Candidates<'a> {
+    type Item = CandidateMatch;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Candidates::CandidateStack(candidates) => candidates.next(),
+            Candidates::Banded(candidates) => candidates.next(),
+            Candidates::BitapSubstitutions(candidates) => candidates.next(),
+            Candidates::EmptyText(candidate) => candidate.take(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod candidates_tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_engine() {
+        let pattern = Pattern::literal("pattern");
+        let long_pattern = Pattern::literal(&"pattern".repeat(10));
+
+        let options = FuzzySearchOptions::new(2);
+        assert_eq!(Engine::Bitap, auto_engine(&pattern, false, &options));
+        assert_eq!(Engine::Myers, auto_engine(&long_pattern, false, &options));
+        assert_eq!(
+            Engine::CandidateStack,
+            auto_engine(&pattern, true, &options)
+        );
+
+        let options = FuzzySearchOptions::new(10);
+        assert_eq!(Engine::Myers, auto_engine(&pattern, false, &options));
+
+        let options = FuzzySearchOptions::with_limits(2, None, Some(0), None);
+        assert_eq!(Engine::Bitap, auto_engine(&pattern, false, &options));
+        assert_eq!(Engine::Myers, auto_engine(&long_pattern, false, &options));
+
+        let options = FuzzySearchOptions::with_limits(10, None, Some(0), Some(0));
+        assert_eq!(Engine::Bitap, auto_engine(&pattern, false, &options));
+
+        let mut options = FuzzySearchOptions::new(2);
+        options.layout_tolerant = true;
+        assert_eq!(
+            Engine::CandidateStack,
+            auto_engine(&pattern, false, &options)
+        );
+        assert_eq!(
+            Engine::CandidateStack,
+            auto_engine(
+                &Pattern::parse("pat*{0,3}tern").unwrap(),
+                false,
+                &FuzzySearchOptions::new(2)
+            )
+        );
+    }
+
+    #[test]
+    fn test_myers_repetitive_text() {
+        let pattern = "acgtacgtaacgtacgttacgtacga";
+        let text = "acgtacgtaacgtacgtt".repeat(300).chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(7);
+        options.engine = Engine::Myers;
+
+        let Candidates::Banded(mut candidates) = Candidates::find(
+            &Pattern::literal(pattern),
+            Cow::Borrowed(&text),
+            None,
+            &options,
+        ) else {
+            panic!("myers should hand its ends to the banded dp");
+        };
+
+        // the traceback over the end windows takes a bounded number of steps, however many partial matches the text has
+        assert!(candidates.by_ref().count() > 0);
+        assert!(candidates.cells() <= 2 * text.len() * (pattern.len() + 1));
+    }
+}
@@ -0,0 +1,465 @@
+use std::cmp::Ordering;
+
+use crate::pattern::PatternError;
+
+// the unicode category Nd (Unicode 16.0), char::is_numeric also contains other numbers like ½
+const DECIMAL_DIGITS: &[(char, char)] = &[
+    ('0', '9'),
+    ('\u{0660}', '\u{0669}'),
+    ('\u{06f0}', '\u{06f9}'),
+    ('\u{07c0}', '\u{07c9}'),
+    ('\u{0966}', '\u{096f}'),
+    ('\u{09e6}', '\u{09ef}'),
+    ('\u{0a66}', '\u{0a6f}'),
+    ('\u{0ae6}', '\u{0aef}'),
+    ('\u{0b66}', '\u{0b6f}'),
+    ('\u{0be6}', '\u{0bef}'),
+    ('\u{0c66}', '\u{0c6f}'),
+    ('\u{0ce6}', '\u{0cef}'),
+    ('\u{0d66}', '\u{0d6f}'),
+    ('\u{0de6}', '\u{0def}'),
+    ('\u{0e50}', '\u{0e59}'),
+    ('\u{0ed0}', '\u{0ed9}'),
+    ('\u{0f20}', '\u{0f29}'),
+    ('\u{1040}', '\u{1049}'),
+    ('\u{1090}', '\u{1099}'),
+    ('\u{17e0}', '\u{17e9}'),
+    ('\u{1810}', '\u{1819}'),
+    ('\u{1946}', '\u{194f}'),
+    ('\u{19d0}', '\u{19d9}'),
+    ('\u{1a80}', '\u{1a89}'),
+    ('\u{1a90}', '\u{1a99}'),
+    ('\u{1b50}', '\u{1b59}'),
+    ('\u{1bb0}', '\u{1bb9}'),
+    ('\u{1c40}', '\u{1c49}'),
+    ('\u{1c50}', '\u{1c59}'),
+    ('\u{a620}', '\u{a629}'),
+    ('\u{a8d0}', '\u{a8d9}'),
+    ('\u{a900}', '\u{a909}'),
+    ('\u{a9d0}', '\u{a9d9}'),
+    ('\u{a9f0}', '\u{a9f9}'),
+    ('\u{aa50}', '\u{aa59}'),
+    ('\u{abf0}', '\u{abf9}'),
+    ('\u{ff10}', '\u{ff19}'),
+    ('\u{104a0}', '\u{104a9}'),
+    ('\u{10d30}', '\u{10d39}'),
+    ('\u{10d40}', '\u{10d49}'),
+    ('\u{11066}', '\u{1106f}'),
+    ('\u{110f0}', '\u{110f9}'),
+    ('\u{11136}', '\u{1113f}'),
+    ('\u{111d0}', '\u{111d9}'),
+    ('\u{112f0}', '\u{112f9}'),
+    ('\u{11450}', '\u{11459}'),
+    ('\u{114d0}', '\u{114d9}'),
+    ('\u{11650}', '\u{11659}'),
+    ('\u{116c0}', '\u{116c9}'),
+    ('\u{116d0}', '\u{116e3}'),
+    ('\u{11730}', '\u{11739}'),
+    ('\u{118e0}', '\u{118e9}'),
+    ('\u{11950}', '\u{11959}'),
+    ('\u{11bf0}', '\u{11bf9}'),
+    ('\u{11c50}', '\u{11c59}'),
+    ('\u{11d50}', '\u{11d59}'),
+    ('\u{11da0}', '\u{11da9}'),
+    ('\u{11f50}', '\u{11f59}'),
+    ('\u{16130}', '\u{16139}'),
+    ('\u{16a60}', '\u{16a69}'),
+    ('\u{16ac0}', '\u{16ac9}'),
+    ('\u{16b50}', '\u{16b59}'),
+    ('\u{16d70}', '\u{16d79}'),
+    ('\u{1ccf0}', '\u{1ccf9}'),
+    ('\u{1d7ce}', '\u{1d7ff}'),
+    ('\u{1e140}', '\u{1e149}'),
+    ('\u{1e2f0}', '\u{1e2f9}'),
+    ('\u{1e4f0}', '\u{1e4f9}'),
+    ('\u{1e5f1}', '\u{1e5fa}'),
+    ('\u{1e950}', '\u{1e959}'),
+    ('\u{1fbf0}', '\u{1fbf9}'),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharCategory {
+    /// Decimal digits, the unicode category Nd
+    Digit,
+    /// Digits, letter numbers like Ⅻ and other numbers like ½, the unicode category N
+    Number,
+    Alphabetic,
+    Word,
+    Whitespace,
+    /// Space, line and paragraph separators, the unicode category Z. Unlike whitespace it does not contain tabs and line breaks
+    Separator,
+    Uppercase,
+    Lowercase,
+    Control,
+}
+
+impl CharCategory {
+    #[inline(always)]
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharCategory::Digit => {
+                c.is_ascii_digit()
+                    || DECIMAL_DIGITS
+                        .binary_search_by(|(start, end)| {
+                            if *end < c {
+                                Ordering::Less
+                            } else if *start > c {
+                                Ordering::Greater
+                            } else {
+                                Ordering::Equal
+                            }
+                        })
+                        .is_ok()
+            }
+            CharCategory::Number => c.is_numeric(),
+            CharCategory::Alphabetic => c.is_alphabetic(),
+            CharCategory::Word => c.is_alphanumeric() || c == '_',
+            CharCategory::Whitespace => c.is_whitespace(),
+            CharCategory::Separator => matches!(
+                c,
+                ' ' | '\u{a0}' | '\u{1680}' | '\u{2000}'
+                    ..='\u{200a}' | '\u{2028}' | '\u{2029}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+            ),
+            CharCategory::Uppercase => c.is_uppercase(),
+            CharCategory::Lowercase => c.is_lowercase(),
+            CharCategory::Control => c.is_control(),
+        }
+    }
+
+    /// Shorthand escapes usable both in patterns and inside brackets, eg `\d`.
+    /// Any other escaped char is a literal, so letters, upper and lower case are only available as `\p{..}`
+    fn from_escape(c: char) -> Option<Self> {
+        match c {
+            'd' => Some(CharCategory::Digit),
+            'w' => Some(CharCategory::Word),
+            's' => Some(CharCategory::Whitespace),
+            _ => None,
+        }
+    }
+
+    /// Unicode general category names supported in `\p{..}`
+    fn from_unicode_name(name: &str) -> Option<Self> {
+        match name {
+            "L" | "Letter" => Some(CharCategory::Alphabetic),
+            "Lu" | "Uppercase_Letter" => Some(CharCategory::Uppercase),
+            "Ll" | "Lowercase_Letter" => Some(CharCategory::Lowercase),
+            "N" | "Number" => Some(CharCategory::Number),
+            "Nd" | "Decimal_Number" => Some(CharCategory::Digit),
+            "Z" | "Separator" => Some(CharCategory::Separator),
+            "Cc" | "Control" => Some(CharCategory::Control),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CharClass {
+    ranges: Vec<(char, char)>,
+    categories: Vec<CharCategory>,
+    negated: bool,
+    case_insensitive: bool,
+}
+
+impl CharClass {
+    pub fn from_chars(chars: &str) -> Self {
+        CharClass {
+            ranges: chars.chars().map(|c| (c, c)).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_range(from: char, to: char) -> Self {
+        CharClass {
+            ranges: vec![(from, to)],
+            ..Default::default()
+        }
+    }
+
+    pub fn from_category(category: CharCategory) -> Self {
+        CharClass {
+            categories: vec![category],
+            ..Default::default()
+        }
+    }
+
+    pub fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+
+    /// Class which also contains the upper and lowercase variants of its chars
+    pub fn ignore_case(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    pub fn categories(&self) -> &[CharCategory] {
+        &self.categories
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, c: char) -> bool {
+        let contains = self.contains_exact(c)
+            || (self.case_insensitive
+                && (Self::single(c.to_uppercase()).is_some_and(|u| self.contains_exact(u))
+                    || Self::single(c.to_lowercase()).is_some_and(|l| self.contains_exact(l))));
+
+        contains != self.negated
+    }
+
+    #[inline(always)]
+    fn contains_exact(&self, c: char) -> bool {
+        self.ranges.iter().any(|(from, to)| *from <= c && c <= *to)
+            || self.categories.iter().any(|category| category.contains(c))
+    }
+
+    fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Parses an escape following a `\`, eg `d` or `p{Lu}`. Returns the class and the number of chars consumed
+    pub(crate) fn parse_escape(
+        chars: &[char],
+        start: usize,
+    ) -> Option<Result<(Self, usize), PatternError>> {
+        match chars.get(start)? {
+            'p' | 'P' => Some(Self::parse_unicode_category(chars, start)),
+            c => {
+                CharCategory::from_escape(*c).map(|category| Ok((Self::from_category(category), 1)))
+            }
+        }
+    }
+
+    fn parse_unicode_category(chars: &[char], start: usize) -> Result<(Self, usize), PatternError> {
+        let invalid = PatternError::InvalidClass(start);
+
+        if chars.get(start + 1) != Some(&'{') {
+            return Err(invalid);
+        }
+
+        let close = chars[start..]
+            .iter()
+            .position(|c| *c == '}')
+            .map(|p| p + start)
+            .ok_or(invalid.clone())?;
+
+        let name = chars[start + 2..close].iter().collect::<String>();
+        let category = CharCategory::from_unicode_name(&name).ok_or(invalid)?;
+        let class = Self::from_category(category);
+
+        let class = match chars[start] {
+            'P' => class.negate(),
+            _ => class,
+        };
+
+        Ok((class, close - start + 1))
+    }
+
+    /// Parses a bracketed class starting at the `[`, eg `[^0-9OIl]`. Returns the class and the number of chars consumed
+    pub(crate) fn parse_bracket(
+        chars: &[char],
+        start: usize,
+    ) -> Result<(Self, usize), PatternError> {
+        let mut class = CharClass::default();
+        let mut index = start + 1;
+
+        if chars.get(index) == Some(&'^') {
+            class.negated = true;
+            index += 1;
+        }
+
+        loop {
+            let c = *chars
+                .get(index)
+                .ok_or(PatternError::UnterminatedClass(start))?;
+
+            let from = match c {
+                ']' if index > start + 1 + class.negated as usize => break,
+                '\\' => {
+                    if let Some(escape) = Self::parse_escape(chars, index + 1) {
+                        let (escaped_class, length) = escape?;
+
+                        if escaped_class.negated {
+                            return Err(PatternError::InvalidClass(index));
+                        }
+
+                        class.categories.extend(escaped_class.categories);
+                        index += length + 1;
+                        continue;
+                    }
+
+                    index += 1;
+                    *chars.get(index).ok_or(PatternError::TrailingEscape)?
+                }
+                c => c,
+            };
+
+            index += 1;
+
+            // a - at the start or end of the class is a literal
+            let is_range =
+                chars.get(index) == Some(&'-') && chars.get(index + 1).is_some_and(|c| *c != ']');
+
+            if is_range {
+                let to = match chars[index + 1] {
+                    '\\' => {
+                        index += 1;
+                        *chars.get(index + 1).ok_or(PatternError::TrailingEscape)?
+                    }
+                    c => c,
+                };
+
+                if to < from {
+                    return Err(PatternError::InvalidClass(index));
+                }
+
+                class.ranges.push((from, to));
+                index += 2;
+            } else {
+                class.ranges.push((from, from));
+            }
+        }
+
+        Ok((class, index - start + 1))
+    }
+}
+
+#[cfg(test)]
+mod char_class_tests {
+    use super::*;
+
+    fn parse(class: &str) -> CharClass {
+        let chars = class.chars().collect::<Vec<_>>();
+        let (class, length) = CharClass::parse_bracket(&chars, 0).unwrap();
+        assert_eq!(chars.len(), length);
+        class
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let class = parse("[0-9OIl]");
+
+        assert!(class.contains('0'));
+        assert!(class.contains('5'));
+        assert!(class.contains('O'));
+        assert!(class.contains('l'));
+        assert!(!class.contains('o'));
+        assert!(!class.contains('a'));
+    }
+
+    #[test]
+    fn test_parse_negated() {
+        let class = parse("[^aeiou]");
+
+        assert!(!class.contains('a'));
+        assert!(class.contains('b'));
+    }
+
+    #[test]
+    fn test_parse_literal_dash_and_bracket() {
+        let class = parse("[]a-]");
+
+        assert!(class.contains(']'));
+        assert!(class.contains('a'));
+        assert!(class.contains('-'));
+        assert!(!class.contains('b'));
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let class = parse(r"[\d\]\-x]");
+
+        assert!(class.contains('7'));
+        assert!(class.contains('٣'));
+        assert!(!class.contains('½'));
+        assert!(!class.contains('Ⅻ'));
+        assert!(class.contains(']'));
+        assert!(class.contains('-'));
+        assert!(class.contains('x'));
+        assert!(!class.contains('y'));
+    }
+
+    #[test]
+    fn test_parse_unicode_category() {
+        let chars = "p{Lu}".chars().collect::<Vec<_>>();
+        let (class, length) = CharClass::parse_escape(&chars, 0).unwrap().unwrap();
+
+        assert_eq!(5, length);
+        assert!(class.contains('Ä'));
+        assert!(!class.contains('ä'));
+
+        let chars = "P{L}".chars().collect::<Vec<_>>();
+        let (class, _) = CharClass::parse_escape(&chars, 0).unwrap().unwrap();
+
+        assert!(!class.contains('ä'));
+        assert!(class.contains('1'));
+
+        let chars = "p{N}".chars().collect::<Vec<_>>();
+        let (class, _) = CharClass::parse_escape(&chars, 0).unwrap().unwrap();
+
+        assert!(class.contains('7'));
+        assert!(class.contains('½'));
+        assert!(class.contains('Ⅻ'));
+
+        let chars = "p{Z}".chars().collect::<Vec<_>>();
+        let (class, _) = CharClass::parse_escape(&chars, 0).unwrap().unwrap();
+
+        assert!(class.contains(' '));
+        assert!(class.contains('\u{3000}'));
+        assert!(!class.contains('\t'));
+        assert!(!class.contains('\n'));
+    }
+
+    #[test]
+    fn test_decimal_digits() {
+        let digit = CharCategory::Digit;
+
+        assert!(digit.contains('0'));
+        assert!(digit.contains('٩'));
+        assert!(digit.contains('\u{1d7ff}'));
+        assert!(!digit.contains('/'));
+        assert!(!digit.contains('²'));
+        assert!(!digit.contains('\u{1d800}'));
+        assert!(DECIMAL_DIGITS.windows(2).all(|w| w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let class = parse("[A-C]").ignore_case();
+
+        assert!(class.contains('b'));
+        assert!(class.contains('B'));
+        assert!(!class.contains('d'));
+
+        let class = parse("[^a]").ignore_case();
+
+        assert!(!class.contains('A'));
+        assert!(class.contains('b'));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse_error = |class: &str| {
+            CharClass::parse_bracket(&class.chars().collect::<Vec<_>>(), 0).unwrap_err()
+        };
+
+        assert_eq!(parse_error("[abc"), PatternError::UnterminatedClass(0));
+        assert_eq!(parse_error("[z-a]"), PatternError::InvalidClass(2));
+        assert_eq!(parse_error(r"[\p{Foo}]"), PatternError::InvalidClass(2));
+    }
+}
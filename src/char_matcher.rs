@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::{fuzzy_search_options::CaseFolding, normalized_text};
+
+/// Decides whether a pattern char and a text char are considered equal.
+/// When no matcher is set in the options, the engines use plain equality with their fast paths
+pub trait CharMatcher: Send + Sync {
+    fn matches(&self, pattern: char, text: char) -> bool;
+}
+
+impl<F: Fn(char, char) -> bool + Send + Sync> CharMatcher for F {
+    fn matches(&self, pattern: char, text: char) -> bool {
+        self(pattern, text)
+    }
+}
+
+/// Plain equality
+pub struct ExactMatcher;
+
+impl CharMatcher for ExactMatcher {
+    #[inline(always)]
+    fn matches(&self, pattern: char, text: char) -> bool {
+        pattern == text
+    }
+}
+
+/// Chars are equal if their simple case foldings are equal
+pub struct CaseInsensitiveMatcher;
+
+impl CharMatcher for CaseInsensitiveMatcher {
+    fn matches(&self, pattern: char, text: char) -> bool {
+        pattern == text
+            || normalized_text::fold_case(pattern, CaseFolding::Simple)
+                .eq(normalized_text::fold_case(text, CaseFolding::Simple))
+    }
+}
+
+/// Chars are equal if they are equal without diacritics, eg é matches e
+pub struct DiacriticInsensitiveMatcher;
+
+impl CharMatcher for DiacriticInsensitiveMatcher {
+    fn matches(&self, pattern: char, text: char) -> bool {
+        pattern == text
+            || normalized_text::strip_diacritics(pattern)
+                .eq(normalized_text::strip_diacritics(text))
+    }
+}
+
+/// Chars are equal if they belong to the same group, eg for look-alike glyphs or domain specific equivalences
+pub struct EquivalenceMatcher {
+    groups: HashMap<char, usize>,
+}
+
+impl EquivalenceMatcher {
+    /// Each string is a group of chars which are considered equal, a char can only be in one group
+    ///
+    /// # Panics
+    /// If a char is in more than one group
+    pub fn new(groups: &[&str]) -> Self {
+        let mut chars = HashMap::new();
+
+        for (i, group) in groups.iter().enumerate() {
+            for c in group.chars() {
+                if let Some(other) = chars.insert(c, i) {
+                    assert_eq!(other, i, "{c} is in more than one group");
+                }
+            }
+        }
+
+        Self { groups: chars }
+    }
+
+    /// Common look-alike glyphs, like OCR confusions between digits and letters and latin, greek and cyrillic homoglyphs
+    pub fn look_alikes() -> Self {
+        Self::new(&[
+            "0OoΟοОо",
+            "1lI|ІӀ",
+            "2Z",
+            "5S",
+            "AΑА",
+            "aа",
+            "8BΒВ",
+            "cсϲ",
+            "CϹС",
+            "eе",
+            "EΕЕ",
+            "HΗН",
+            "KΚК",
+            "MΜМ",
+            "NΝ",
+            "pр",
+            "PΡР",
+            "TΤТ",
+            "xх",
+            "XΧХ",
+            "yу",
+        ])
+    }
+}
+
+impl CharMatcher for EquivalenceMatcher {
+    fn matches(&self, pattern: char, text: char) -> bool {
+        pattern == text
+            || self
+                .groups
+                .get(&pattern)
+                .is_some_and(|group| self.groups.get(&text) == Some(group))
+    }
+}
+
+#[cfg(test)]
+mod char_matcher_tests {
+    use super::*;
+
+    #[test]
+    fn test_exact() {
+        assert!(ExactMatcher.matches('a', 'a'));
+        assert!(!ExactMatcher.matches('a', 'A'));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(CaseInsensitiveMatcher.matches('a', 'A'));
+        assert!(CaseInsensitiveMatcher.matches('ß', 'ẞ'));
+        assert!(!CaseInsensitiveMatcher.matches('a', 'b'));
+    }
+
+    #[test]
+    fn test_diacritic_insensitive() {
+        assert!(DiacriticInsensitiveMatcher.matches('o', 'ö'));
+        assert!(DiacriticInsensitiveMatcher.matches('é', 'e'));
+        assert!(!DiacriticInsensitiveMatcher.matches('é', 'E'));
+    }
+
+    #[test]
+    fn test_equivalence() {
+        let matcher = EquivalenceMatcher::look_alikes();
+
+        assert!(matcher.matches('0', 'O'));
+        assert!(matcher.matches('O', 'О')); // cyrillic
+        assert!(matcher.matches('l', '1'));
+        assert!(matcher.matches('8', 'B'));
+        assert!(matcher.matches('8', 'В')); // cyrillic
+        assert!(!matcher.matches('0', '1'));
+        assert!(matcher.matches('q', 'q'));
+        assert!(!matcher.matches('q', 'g'));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_char_in_two_groups() {
+        EquivalenceMatcher::new(&["8B", "BΒВ"]);
+    }
+
+    #[test]
+    fn test_closure() {
+        let vowels = |p: char, t: char| p == t || ("aeiou".contains(p) && "aeiou".contains(t));
+
+        assert!(vowels.matches('a', 'e'));
+        assert!(!vowels.matches('a', 'b'));
+    }
+}
@@ -0,0 +1,436 @@
+use std::{borrow::Cow, cmp::Reverse};
+
+use crate::{
+    candidate_match::{Alignment, CandidateMatch},
+    fuzzy_search_options::FuzzySearchOptions,
+    pattern::{Pattern, PatternElement},
+    prefilter::{next_window_range, Windows},
+};
+
+// starts searched together, the columns of the longest match after each block are computed for both blocks
+const BLOCK_LENGTH: usize = 1024;
+
+/// Column wise levenshtein dp from the end of the text to the start, where each cell holds the cheapest ways to finish the pattern from it.
+/// Only the band of rows within the distance limit is evaluated (Ukkonen's cutoff),
+/// so it runs in O(n * k) expected and O(n * m) worst case time, regardless of how repetitive the text is.
+/// The cells take the same steps as the candidate stack, edits are only made at mismatches and a match can be followed by an insertion,
+/// so the best match of each start and its substitutions, deletions and insertions are the same as the candidate stack finds.
+/// If the options limit an edit kind below the total distance, each cell keeps every combination of edit counts not dominated by another,
+/// since the cheapest way to finish may exceed a limit which another one keeps
+pub struct FuzzySearchBanded<'a> {
+    pattern: Vec<PatternElement>,
+    text_chars: Cow<'a, [char]>,
+    options: &'a FuzzySearchOptions,
+    pareto: bool, // set if edit kinds have limits below the total distance
+    longest_match: usize,
+    columns: [Column; 3], // the column of a text index and the two after it, at the text index modulo 3
+    starts: Vec<(usize, Option<Ending>)>, // best ending of each searched start of the current block, last start first
+    exact_start: Option<usize>, // set if the last searched start had an exact match, the next one then only looks for exact matches
+    current_text_index: usize,  // first start of the next block
+    windows: Option<Windows>,   // match start indexes worth searching, all if None
+    #[cfg(test)]
+    cells: usize,
+}
+
+/// Edits of a way to finish the pattern and the text index where the match ends
+#[derive(Debug, Clone, Copy)]
+struct Ending {
+    alignment: Alignment,
+    end_index: usize,
+}
+
+impl Ending {
+    fn is_better_than(&self, other: &Ending) -> bool {
+        (self.alignment.distance(), Reverse(self.end_index))
+            < (other.alignment.distance(), Reverse(other.end_index))
+    }
+
+    /// Every match this ending leads to is also reached by the other one with no more edits of any kind
+    fn is_dominated_by(&self, other: &Ending) -> bool {
+        let (a, b) = (&self.alignment, &other.alignment);
+
+        b.substitutions <= a.substitutions
+            && b.deletions <= a.deletions
+            && b.insertions <= a.insertions
+            && (a != b || other.end_index >= self.end_index)
+    }
+}
+
+/// Endings of each pattern index at one text index, the rows below low are empty
+#[derive(Default)]
+struct Column {
+    rows: Vec<Vec<Ending>>,
+    low: usize,
+}
+
+impl Column {
+    fn new(pattern_length: usize) -> Self {
+        Self {
+            rows: vec![Vec::new(); pattern_length + 1],
+            low: pattern_length + 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        for row in &mut self.rows[self.low..] {
+            row.clear();
+        }
+
+        self.low = self.rows.len();
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Match,
+    Substitution,
+    Deletion,
+    Insertion,
+}
+
+impl<'a> FuzzySearchBanded<'a> {
+    /// The text is expected to be normalized already, the pattern is normalized here
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        let pattern = pattern.prepare(options).elements().to_vec();
+        let max_distance = options.max_total_distance;
+
+        Self {
+            pareto: options.max_substitutions < max_distance
+                || options.max_deletions < max_distance
+                || options.max_insertions < max_distance,
+            longest_match: pattern.len() + options.max_insertions.min(max_distance),
+            columns: std::array::from_fn(|_| Column::new(pattern.len())),
+            starts: Vec::new(),
+            exact_start: None,
+            // the empty pattern has no matches
+            current_text_index: if pattern.is_empty() {
+                text_chars.len()
+            } else {
+                0
+            },
+            windows: None,
+            pattern,
+            text_chars,
+            options,
+            #[cfg(test)]
+            cells: 0,
+        }
+    }
+
+    /// Only searches matches starting inside the windows
+    pub fn with_windows(mut self, windows: Option<Windows>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Continues after an exact match starting at the index before the windows,
+    /// so the first start only looks for exact matches unless a start was skipped in between
+    pub fn after_exact_match(mut self, start_index: usize) -> Self {
+        self.exact_start = Some(start_index);
+        self
+    }
+
+    /// Dp cells computed so far
+    #[cfg(test)]
+    pub fn cells(&self) -> usize {
+        self.cells
+    }
+
+    /// Computes the best endings of the starts in the next block, false if there are no starts left
+    fn search_block(&mut self) -> bool {
+        let text_length = self.text_chars.len();
+        let Some(range) =
+            next_window_range(&mut self.windows, self.current_text_index, text_length)
+        else {
+            return false;
+        };
+
+        let mut block = range.start..range.end.min(range.start + BLOCK_LENGTH);
+
+        if self.options.is_anchored_start() {
+            // only the start of the text is allowed
+            block.end = block.end.min(1);
+
+            if block.is_empty() {
+                return false;
+            }
+        }
+
+        // no match starting in the block ends after the longest match from its last start
+        let last_column = (block.end - 1 + self.longest_match).min(text_length);
+        self.columns[(last_column + 1) % 3].clear();
+        self.columns[(last_column + 2) % 3].clear();
+
+        for text_index in (block.start..=last_column).rev() {
+            self.compute_column(text_index);
+
+            if text_index < block.end
+                && self
+                    .options
+                    .allows_match_start(&self.text_chars, text_index)
+            {
+                let best = self.columns[text_index % 3].rows[0]
+                    .iter()
+                    .min_by_key(|e| (e.alignment.distance(), Reverse(e.end_index)))
+                    .copied();
+
+                self.starts.push((text_index, best));
+            }
+        }
+
+        self.current_text_index = block.end;
+        true
+    }
+
+    /// Computes the endings of the column at the text index from the two columns after it
+    fn compute_column(&mut self, text_index: usize) {
+        let mut column = std::mem::take(&mut self.columns[text_index % 3]);
+        column.clear();
+
+        let next = &self.columns[(text_index + 1) % 3];
+        let after = &self.columns[(text_index + 2) % 3];
+        let options = self.options;
+        let pattern_length = self.pattern.len();
+        let text_char = self.text_chars.get(text_index).copied();
+
+        if options.allows_match_end(&self.text_chars, text_index) {
+            column.rows[pattern_length].push(Ending {
+                alignment: Alignment::default(),
+                end_index: text_index,
+            });
+            column.low = pattern_length;
+        }
+
+        for pattern_index in (0..pattern_length).rev() {
+            let (rows, later_rows) = column.rows.split_at_mut(pattern_index + 1);
+            let (row, following) = (&mut rows[pattern_index], &later_rows[0]);
+
+            // past the band of the later columns only deletions can reach this row
+            if following.is_empty() && pattern_index + 1 < next.low.min(after.low) {
+                break;
+            }
+
+            #[cfg(test)]
+            {
+                self.cells += 1;
+            }
+
+            let element = &self.pattern[pattern_index];
+            let mut extend = |endings: &[Ending], step: Step| {
+                for ending in endings {
+                    if let Some(ending) = Self::step(ending, step, options) {
+                        Self::insert(row, ending, self.pareto);
+                    }
+                }
+            };
+
+            // in the order the candidate stack explores them, so ties go to the same ending
+            match text_char {
+                Some(c) if element.matches_with(c, options.char_matcher.as_deref()) => {
+                    extend(&next.rows[pattern_index + 1], Step::Match);
+                    extend(&after.rows[pattern_index + 1], Step::Insertion);
+                }
+                Some(_) => {
+                    extend(&next.rows[pattern_index + 1], Step::Substitution);
+                    extend(following, Step::Deletion);
+                }
+                None => extend(following, Step::Deletion),
+            }
+
+            if pattern_index == 0 && options.is_anchored_start() && text_char.is_some() {
+                // matches anchored to the start of the text can begin with extra chars
+                extend(&next.rows[0], Step::Insertion);
+            }
+
+            if !row.is_empty() {
+                column.low = pattern_index;
+            }
+        }
+
+        self.columns[text_index % 3] = column;
+    }
+
+    /// The ending after taking the step before it, None if that exceeds a limit
+    #[inline(always)]
+    fn step(ending: &Ending, step: Step, options: &FuzzySearchOptions) -> Option<Ending> {
+        let mut alignment = ending.alignment;
+
+        match step {
+            Step::Match => {}
+            Step::Substitution => alignment.substitutions += 1,
+            Step::Deletion => alignment.deletions += 1,
+            Step::Insertion => alignment.insertions += 1,
+        }
+
+        let allowed = alignment.distance() <= options.max_total_distance
+            && alignment.substitutions <= options.max_substitutions
+            && alignment.deletions <= options.max_deletions
+            && alignment.insertions <= options.max_insertions;
+
+        allowed.then_some(Ending {
+            alignment,
+            end_index: ending.end_index,
+        })
+    }
+
+    /// Adds the ending to the row unless a better one is there already, earlier endings win ties
+    #[inline(always)]
+    fn insert(row: &mut Vec<Ending>, ending: Ending, pareto: bool) {
+        if !pareto {
+            match row.first_mut() {
+                Some(best) if ending.is_better_than(best) => *best = ending,
+                Some(_) => {}
+                None => row.push(ending),
+            }
+        } else if !row.iter().any(|e| ending.is_dominated_by(e)) {
+            row.retain(|e| !e.is_dominated_by(&ending));
+            row.push(ending);
+        }
+    }
+}
+
+impl<'a> Iterator for FuzzySearchBanded<'a> {
+    type Item = CandidateMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some((start_index, best)) = self.starts.pop() {
+                // like the candidate stack, the start after an exact match only looks for exact matches unless a start was skipped in between
+                let exact_only = self.exact_start.is_some_and(|exact_start| {
+                    !(exact_start + 1..start_index)
+                        .any(|i| self.options.allows_match_start(&self.text_chars, i))
+                });
+                self.exact_start = None;
+
+                if let Some(best) = best.filter(|b| !exact_only || b.alignment.distance() == 0) {
+                    if best.alignment.distance() == 0 {
+                        self.exact_start = Some(start_index);
+                    }
+
+                    return Some(CandidateMatch::from_alignment(
+                        start_index,
+                        best.end_index,
+                        self.pattern.len(),
+                        best.alignment,
+                    ));
+                }
+            }
+
+            if !self.search_block() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_banded_tests {
+    use super::*;
+    use crate::{
+        fuzzy_search_levenshtein::FuzzySearchLevenshtein, fuzzy_search_options::SearchMode,
+        match_consolidator::MatchConsolidator,
+    };
+
+    type Summary = (usize, usize, usize, usize, usize, usize);
+
+    fn summary(c: CandidateMatch) -> Summary {
+        (
+            c.start_index,
+            c.text_index,
+            c.distance,
+            c.substitutions,
+            c.deletions,
+            c.insertions,
+        )
+    }
+
+    fn find(pattern: &str, text: &str, options: &FuzzySearchOptions) -> Vec<Summary> {
+        let text = text.chars().collect::<Vec<_>>();
+        let banded = FuzzySearchBanded::find(&Pattern::literal(pattern), Cow::Owned(text), options);
+
+        MatchConsolidator::consolidate(options.max_total_distance, banded)
+            .map(summary)
+            .collect()
+    }
+
+    fn candidate_stack(pattern: &str, text: &str, options: &FuzzySearchOptions) -> Vec<Summary> {
+        let text = text.chars().collect::<Vec<_>>();
+        let candidates = FuzzySearchLevenshtein::find(
+            &Pattern::literal(pattern),
+            Cow::Owned(text),
+            None,
+            options,
+        );
+
+        MatchConsolidator::consolidate(options.max_total_distance, candidates)
+            .map(summary)
+            .collect()
+    }
+
+    #[test]
+    fn test_exact() {
+        let options = FuzzySearchOptions::new(0);
+
+        assert_eq!(
+            vec![(3, 10, 0, 0, 0, 0)],
+            find("pattern", "---pattern--", &options)
+        );
+    }
+
+    #[test]
+    fn test_same_as_candidate_stack() {
+        let text = "--patern--xpattttern-pattxrn--ptrn-pat-tern";
+
+        for search_mode in [SearchMode::Anywhere, SearchMode::Prefix, SearchMode::Suffix] {
+            for max_distance in 0..5 {
+                let mut options = FuzzySearchOptions::new(max_distance);
+                options.search_mode = search_mode;
+
+                assert_eq!(
+                    candidate_stack("pattern", text, &options),
+                    find("pattern", text, &options),
+                    "{search_mode:?} {max_distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_edit_limits() {
+        let text = "--patern--xpattttern-pattxrn--ptrn-pat-tern";
+
+        for limits in [(0, 3, 3), (3, 0, 3), (3, 3, 0), (1, 1, 1), (2, 0, 1)] {
+            let options =
+                FuzzySearchOptions::with_limits(3, Some(limits.0), Some(limits.1), Some(limits.2));
+
+            let matches = find("pattern", text, &options);
+            assert_eq!(
+                candidate_stack("pattern", text, &options),
+                matches,
+                "{limits:?}"
+            );
+            assert!(matches
+                .iter()
+                .all(|m| m.3 <= limits.0 && m.4 <= limits.1 && m.5 <= limits.2));
+        }
+    }
+
+    #[test]
+    fn test_repetitive_text() {
+        let options = FuzzySearchOptions::new(7);
+        let pattern = "acgtacgtaacgtacgttacgtacga";
+        let text = "acgtacgtaacgtacgtt".repeat(300).chars().collect::<Vec<_>>();
+
+        let mut banded =
+            FuzzySearchBanded::find(&Pattern::literal(pattern), Cow::Borrowed(&text), &options);
+        let matches = banded.by_ref().count();
+
+        // every column is computed at most twice, once for its own block and once for the block before it
+        assert!(matches > 0);
+        assert!(banded.cells <= 2 * text.len() * (pattern.len() + 1));
+    }
+}
@@ -0,0 +1,355 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    candidate_match::{Alignment, CandidateMatch},
+    fuzzy_search_options::FuzzySearchOptions,
+    pattern::{Pattern, PatternElement},
+    prefilter::{next_window_index, Windows},
+};
+
+pub(crate) const MAX_PATTERN_LENGTH: usize = 64;
+
+/// Wu-Manber bitap search for patterns of up to 64 chars, with one state word per error level.
+/// If the options allow neither insertions nor deletions only substitutions are tracked, otherwise levenshtein distance.
+/// Finds the end positions within the distance, the banded dp then traces the matches ending there back to their starts.
+/// With only substitutions every match has the pattern length, so the matches come straight from the states
+pub struct FuzzySearchBitap<'a> {
+    pattern: Vec<PatternElement>,
+    text_chars: Cow<'a, [char]>,
+    options: &'a FuzzySearchOptions,
+    substitutions_only: bool,
+    states: Vec<u64>, // bit i of level d is set if the first i + 1 pattern chars match the text read so far with at most d edits
+    last_bit: u64,
+    ascii_masks: Vec<u64>,
+    masks: HashMap<char, u64>, // masks for other chars, built when first seen
+    current_text_index: usize,
+    windows: Option<Windows>, // text ranges worth scanning, all if None
+}
+
+impl<'a> FuzzySearchBitap<'a> {
+    /// The text is expected to be normalized already, the pattern is normalized here
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        let pattern = pattern.prepare(options).elements().to_vec();
+        assert!(
+            pattern.len() <= MAX_PATTERN_LENGTH,
+            "bitap supports patterns of up to {MAX_PATTERN_LENGTH} chars"
+        );
+
+        let substitutions_only = options.max_deletions == 0 && options.max_insertions == 0;
+        let max_distance = match substitutions_only {
+            true => options.max_substitutions_only_distance(),
+            false => options.max_total_distance,
+        };
+
+        // deleting the whole pattern is enough anywhere, but anchored matches can also pay for leading text chars
+        let levels = match options.is_anchored_start() && !substitutions_only {
+            true => max_distance + 1,
+            false => max_distance.min(pattern.len()) + 1,
+        };
+
+        let mut search = Self {
+            states: vec![0; levels],
+            last_bit: 1 << pattern.len().saturating_sub(1),
+            ascii_masks: Vec::new(),
+            masks: HashMap::new(),
+            windows: None,
+            current_text_index: if pattern.is_empty() {
+                text_chars.len() + 1
+            } else {
+                0
+            },
+            substitutions_only,
+            pattern,
+            text_chars,
+            options,
+        };
+
+        search.reset_states();
+        search.ascii_masks = (0..128u8).map(|c| search.build_mask(c as char)).collect();
+        search
+    }
+
+    /// Only scans the text inside the windows, the windows should cover the whole text range of the matches
+    pub fn with_windows(mut self, windows: Option<Windows>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// The matches of a substitutions only search, starting inside the start windows if given
+    pub fn substitution_matches(
+        self,
+        start_windows: Option<Windows>,
+    ) -> FuzzySearchBitapMatches<'a> {
+        debug_assert!(self.substitutions_only);
+
+        FuzzySearchBitapMatches {
+            bitap: self,
+            start_windows,
+            exact_start: None,
+        }
+    }
+
+    fn reset_states(&mut self) {
+        self.states.fill(0);
+
+        if !self.substitutions_only {
+            // the first d pattern chars can be deleted before the text starts
+            for d in 1..self.states.len() {
+                self.states[d] = (self.states[d - 1] << 1) | 1;
+            }
+        }
+    }
+
+    fn build_mask(&self, c: char) -> u64 {
+        self.pattern
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| element.matches_with(c, self.options.char_matcher.as_deref()))
+            .fold(0, |mask, (i, _)| mask | 1 << i)
+    }
+
+    #[inline(always)]
+    fn mask(&mut self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii_masks[c as usize]
+        } else if let Some(mask) = self.masks.get(&c) {
+            *mask
+        } else {
+            let mask = self.build_mask(c);
+            self.masks.insert(c, mask);
+            mask
+        }
+    }
+
+    /// 1 if the empty pattern prefix is within d edits after reading text_index chars.
+    /// Anywhere in the text a match can start for free, anchored matches pay for leading text chars with insertions
+    #[inline(always)]
+    fn empty_prefix(&self, d: usize, text_index: usize) -> u64 {
+        match self.options.is_anchored_start() {
+            true if self.substitutions_only => (text_index == 0) as u64,
+            true => (text_index <= d) as u64,
+            false => 1,
+        }
+    }
+
+    /// Advances the states of all error levels by the text char at the current index
+    #[inline(always)]
+    fn advance(&mut self, c: char) {
+        let mask = self.mask(c);
+        let text_index = self.current_text_index;
+        let (mut previous_old, mut previous_new) = (0, 0);
+
+        for d in 0..self.states.len() {
+            let old = self.states[d];
+            let mut new = ((old << 1) | self.empty_prefix(d, text_index)) & mask;
+
+            if d > 0 {
+                // substitution
+                new |= (previous_old << 1) | self.empty_prefix(d - 1, text_index);
+
+                if !self.substitutions_only {
+                    // insertion of the text char, and deletion of a pattern char
+                    new |= previous_old
+                        | (previous_new << 1)
+                        | self.empty_prefix(d - 1, text_index + 1);
+                }
+            }
+
+            self.states[d] = new;
+            previous_old = old;
+            previous_new = new;
+        }
+    }
+
+    /// Next end of a match within the distance and the lowest error level with a match ending there
+    fn next_end(&mut self) -> Option<(usize, usize)> {
+        while self.current_text_index < self.text_chars.len() {
+            match next_window_index(&mut self.windows, self.current_text_index) {
+                None => break,
+                Some(index) if index > self.current_text_index => {
+                    self.reset_states();
+                    self.current_text_index = index;
+                }
+                _ => {}
+            }
+
+            if self.options.is_anchored_start()
+                && self.current_text_index >= self.pattern.len() + self.states.len() - 1
+            {
+                // anchored matches cannot be longer than this
+                break;
+            }
+
+            self.advance(self.text_chars[self.current_text_index]);
+            self.current_text_index += 1;
+
+            let end_index = self.current_text_index;
+
+            if let Some(level) = self.states.iter().position(|s| s & self.last_bit != 0) {
+                if self.options.allows_match_end(&self.text_chars, end_index) {
+                    return Some((end_index, level));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a> Iterator for FuzzySearchBitap<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_end().map(|(end_index, _)| end_index)
+    }
+}
+
+/// Matches of a substitutions only bitap search, the same as the candidate stack finds
+pub struct FuzzySearchBitapMatches<'a> {
+    bitap: FuzzySearchBitap<'a>,
+    start_windows: Option<Windows>, // match start indexes worth searching, all if None
+    exact_start: Option<usize>,     // start of the last exact match
+}
+
+impl<'a> FuzzySearchBitapMatches<'a> {
+    /// Continues after an exact match starting at the index before the windows,
+    /// so the first start only looks for exact matches unless a start was skipped in between
+    pub fn after_exact_match(mut self, start_index: usize) -> Self {
+        self.exact_start = Some(start_index);
+        self
+    }
+}
+
+impl<'a> Iterator for FuzzySearchBitapMatches<'a> {
+    type Item = CandidateMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pattern_length = self.bitap.pattern.len();
+
+        while let Some((end_index, distance)) = self.bitap.next_end() {
+            let start_index = end_index - pattern_length;
+            let (text, options) = (&self.bitap.text_chars, self.bitap.options);
+
+            if !options.allows_match_start(text, start_index)
+                || next_window_index(&mut self.start_windows, start_index) != Some(start_index)
+            {
+                continue;
+            }
+
+            // like the candidate stack, the start after an exact match only looks for exact matches,
+            // starts in between without a match or outside the windows are searched normally
+            let exact_only = self.exact_start.is_some_and(|exact_start| {
+                !(exact_start + 1..start_index).any(|i| options.allows_match_start(text, i))
+            });
+            self.exact_start = (distance == 0).then_some(start_index);
+
+            if !exact_only || distance == 0 {
+                return Some(CandidateMatch::from_alignment(
+                    start_index,
+                    end_index,
+                    pattern_length,
+                    Alignment {
+                        substitutions: distance,
+                        ..Alignment::default()
+                    },
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_bitap_tests {
+    use super::*;
+    use crate::{fuzzy_search_myers::FuzzySearchMyers, fuzzy_search_options::SearchMode};
+
+    fn find(pattern: &str, text: &str, options: &FuzzySearchOptions) -> Vec<usize> {
+        let text = text.chars().collect::<Vec<_>>();
+
+        FuzzySearchBitap::find(&Pattern::literal(pattern), Cow::Owned(text), options).collect()
+    }
+
+    #[test]
+    fn test_exact() {
+        let options = FuzzySearchOptions::new(0);
+
+        assert_eq!(vec![10], find("pattern", "---pattern--", &options));
+    }
+
+    #[test]
+    fn test_same_ends_as_myers() {
+        let text = "--patern--xpattttern-pattxrn--ptrn-pat-tern";
+
+        for search_mode in [SearchMode::Anywhere, SearchMode::Prefix] {
+            let mut options = FuzzySearchOptions::new(2);
+            options.search_mode = search_mode;
+
+            let bitap = find("pattern", text, &options);
+            let text = text.chars().collect::<Vec<_>>();
+            let myers = FuzzySearchMyers::find(
+                &Pattern::literal("pattern"),
+                Cow::Borrowed(&text),
+                &options,
+            )
+            .collect::<Vec<_>>();
+
+            assert_eq!(myers, bitap);
+        }
+    }
+
+    #[test]
+    fn test_substitutions_only() {
+        let options = FuzzySearchOptions::with_limits(2, Some(2), Some(0), Some(0));
+
+        assert_eq!(
+            vec![17, 26],
+            find("pattern", "--patern--pxttxrn--pattern", &options)
+        );
+    }
+
+    #[test]
+    fn test_substitution_matches() {
+        let options = FuzzySearchOptions::with_limits(2, Some(2), Some(0), Some(0));
+        let text = "--patern--pxttxrn--pattern".chars().collect::<Vec<_>>();
+
+        let matches =
+            FuzzySearchBitap::find(&Pattern::literal("pattern"), Cow::Borrowed(&text), &options)
+                .substitution_matches(None)
+                .map(|c| (c.start_index, c.text_index, c.substitutions))
+                .collect::<Vec<_>>();
+
+        assert_eq!(vec![(10, 17, 2), (19, 26, 0)], matches);
+    }
+
+    #[test]
+    fn test_full_width_pattern() {
+        let options = FuzzySearchOptions::new(2);
+        let pattern = "abcdefghijklmnopqrstuvwxyz".repeat(3)[..64].to_string();
+        let text = "--".to_string() + &pattern.replacen("klm", "kxm", 1) + "--";
+
+        assert!(find(&pattern, &text, &options).contains(&66));
+    }
+
+    #[test]
+    fn test_anchored_start() {
+        let mut options = FuzzySearchOptions::new(1);
+        options.search_mode = SearchMode::Prefix;
+
+        assert_eq!(vec![8], find("pattern", "xpattern pattern", &options));
+        assert!(find("pattern", "xxpattern", &options).is_empty());
+    }
+
+    #[test]
+    fn test_anchored_distance_above_pattern_length() {
+        let mut options = FuzzySearchOptions::new(2);
+        options.search_mode = SearchMode::Whole;
+
+        assert_eq!(vec![3], find("c", "cbc", &options));
+    }
+}
@@ -0,0 +1,315 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    candidates::Candidates,
+    fuzzy_search_options::FuzzySearchOptions,
+    match_consolidator::MatchConsolidator,
+    match_result::GraphemeMatchResult,
+    normalized_text::{NormalizedText, OffsetMap},
+    pattern::Pattern,
+};
+
+// clusters which dont fit in a single char are mapped to chars in the supplementary private use areas
+const FIRST_INTERNED_CHAR: u32 = 0xF0000;
+
+// text clusters which are not in the pattern are only compared against pattern clusters, so they can share one char
+const OTHER_CLUSTER: char = '\u{10FFFF}';
+
+/// Maps each extended grapheme cluster of the pattern to a single char, so every cluster counts as one unit
+#[derive(Default)]
+struct GraphemeInterner {
+    clusters: HashMap<String, char>,
+}
+
+impl GraphemeInterner {
+    fn intern(&mut self, cluster: &str) -> char {
+        match Self::single_char(cluster) {
+            Some(c) => c,
+            None => {
+                let next = char::from_u32(FIRST_INTERNED_CHAR + self.clusters.len() as u32)
+                    .filter(|c| *c != OTHER_CLUSTER)
+                    .expect("too many distinct graphemes in the pattern");
+
+                *self.clusters.entry(cluster.to_string()).or_insert(next)
+            }
+        }
+    }
+
+    /// The char of a text cluster, clusters which are not in the pattern never match
+    fn unit(&self, cluster: &str) -> char {
+        Self::single_char(cluster)
+            .or_else(|| self.clusters.get(cluster).copied())
+            .unwrap_or(OTHER_CLUSTER)
+    }
+
+    fn single_char(cluster: &str) -> Option<char> {
+        let mut chars = cluster.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if (c as u32) < FIRST_INTERNED_CHAR => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Normalizes the text as a whole and segments the normalized text, eg so ß folded to ss is two clusters like in the pattern.
+/// Returns the clusters with their start index in the normalized chars, and the map back to the original chars if the text was normalized
+fn normalized_clusters(
+    text: &str,
+    options: &FuzzySearchOptions,
+) -> (Vec<(usize, String)>, Option<OffsetMap>) {
+    let chars = text.chars().collect::<Vec<_>>();
+    let (normalized, offsets) = match NormalizedText::normalize(&chars, options) {
+        Some(normalized) => (
+            normalized.chars.iter().collect::<String>(),
+            Some(normalized.offsets),
+        ),
+        None => (text.to_string(), None),
+    };
+
+    let mut index = 0;
+    let clusters = normalized
+        .graphemes(true)
+        .map(|cluster| {
+            let start = index;
+            index += cluster.chars().count();
+            (start, cluster.to_string())
+        })
+        .collect();
+
+    (clusters, offsets)
+}
+
+/// Fuzzy search where pattern and text are segmented into extended grapheme clusters.
+/// Each cluster counts as one unit for the distance, and matches never start or end inside a cluster of the original text
+pub struct FuzzySearchGraphemes<'a> {
+    consolidated_matches: MatchConsolidator<Candidates<'a>>,
+    text: &'a str,
+    unit_starts: Vec<usize>, // index of each unit in the normalized chars, followed by their count
+    offsets: Option<OffsetMap>, // set if the text was normalized, maps normalized chars back to the original chars
+    char_bytes: Vec<usize>,     // byte offset of each original char, followed by the text length
+    grapheme_chars: Vec<usize>, // index of the first char of each original grapheme, followed by the char count
+}
+
+impl<'a> FuzzySearchGraphemes<'a> {
+    pub fn find(pattern: &str, text: &'a str, options: &'a FuzzySearchOptions) -> Self {
+        let mut interner = GraphemeInterner::default();
+
+        let (pattern_clusters, _) = normalized_clusters(pattern, options);
+        let pattern_units = pattern_clusters
+            .iter()
+            .map(|(_, cluster)| interner.intern(cluster))
+            .collect::<String>();
+
+        let (text_clusters, offsets) = normalized_clusters(text, options);
+        let (mut unit_starts, text_units): (Vec<_>, Vec<_>) = text_clusters
+            .iter()
+            .map(|(start, cluster)| (*start, interner.unit(cluster)))
+            .unzip();
+        unit_starts.push(
+            text_clusters
+                .last()
+                .map_or(0, |(start, cluster)| start + cluster.chars().count()),
+        );
+
+        let mut char_bytes = text.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+        char_bytes.push(text.len());
+
+        let mut index = 0;
+        let mut grapheme_chars = text
+            .graphemes(true)
+            .map(|cluster| {
+                let start = index;
+                index += cluster.chars().count();
+                start
+            })
+            .collect::<Vec<_>>();
+        grapheme_chars.push(index);
+
+        Self {
+            consolidated_matches: MatchConsolidator::consolidate(
+                options.max_total_distance,
+                Candidates::find(
+                    &Pattern::literal(&pattern_units),
+                    Cow::Owned(text_units),
+                    None,
+                    options,
+                ),
+            ),
+            text,
+            unit_starts,
+            offsets,
+            char_bytes,
+            grapheme_chars,
+        }
+    }
+
+    /// Range of original graphemes covering the range of units
+    fn grapheme_range(&self, start_unit: usize, end_unit: usize) -> (usize, usize) {
+        let (start, end) = (self.unit_starts[start_unit], self.unit_starts[end_unit]);
+        let (start, end) = match &self.offsets {
+            Some(offsets) => offsets.original_range(start, end),
+            None => (start, end),
+        };
+
+        (
+            self.grapheme_chars.partition_point(|c| *c <= start) - 1,
+            self.grapheme_chars.partition_point(|c| *c < end),
+        )
+    }
+}
+
+impl<'a> Iterator for FuzzySearchGraphemes<'a> {
+    type Item = GraphemeMatchResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.consolidated_matches.next().map(|m| {
+            let (start_index, end_index) = self.grapheme_range(m.start_index, m.text_index);
+            let start_byte = self.char_bytes[self.grapheme_chars[start_index]];
+            let end_byte = self.char_bytes[self.grapheme_chars[end_index]];
+
+            GraphemeMatchResult {
+                start_index,
+                end_index,
+                start_byte,
+                end_byte,
+                distance: m.distance,
+                weighted_distance: m.costs.weighted_distance(),
+                match_text: self.text[start_byte..end_byte].to_string(),
+                deletions: m.deletions,
+                substitutions: m.substitutions,
+                insertions: m.insertions,
+                ignored: m.ignored,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_graphemes_tests {
+    use super::*;
+    use crate::fuzzy_search_options::CaseFolding;
+
+    #[test]
+    fn test_family_emoji_is_one_unit() {
+        let text = "we 👩‍👩‍👦‍👦 here";
+        let options = FuzzySearchOptions::new(1);
+
+        let results = FuzzySearchGraphemes::find("we 👨 here", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!(1, results[0].distance);
+        assert_eq!(1, results[0].substitutions);
+        assert_eq!((0, 9), (results[0].start_index, results[0].end_index));
+        assert_eq!(
+            (0, text.len()),
+            (results[0].start_byte, results[0].end_byte)
+        );
+        assert_eq!(text, results[0].match_text);
+    }
+
+    #[test]
+    fn test_pattern_after_grapheme() {
+        let text = "👩‍👩‍👦‍👦PATTERN";
+        let options = FuzzySearchOptions::new(1);
+
+        let results = FuzzySearchGraphemes::find("PATTERN", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!((1, 8), (results[0].start_index, results[0].end_index));
+        assert_eq!((25, 32), (results[0].start_byte, results[0].end_byte));
+        assert_eq!("PATTERN", results[0].match_text);
+    }
+
+    #[test]
+    fn test_match_does_not_split_cluster() {
+        // e followed by a combining acute accent is a single cluster which differs from e
+        let text = "--cafe\u{301}--";
+        let options = FuzzySearchOptions::new(1);
+
+        let results = FuzzySearchGraphemes::find("cafe", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!("cafe\u{301}", results[0].match_text);
+        assert_eq!(1, results[0].distance);
+        assert_eq!((2, 6), (results[0].start_index, results[0].end_index));
+        assert_eq!((2, 8), (results[0].start_byte, results[0].end_byte));
+    }
+
+    #[test]
+    fn test_clusters_with_normalization() {
+        let text = "--CAFE\u{301}--";
+        let mut options = FuzzySearchOptions::new(0);
+        options.case_folding = CaseFolding::Simple;
+        options.ignore_diacritics = true;
+
+        let results = FuzzySearchGraphemes::find("café", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!("CAFE\u{301}", results[0].match_text);
+    }
+
+    #[test]
+    fn test_full_case_folding() {
+        let text = "die Straße und die STRASSE";
+        let mut options = FuzzySearchOptions::new(0);
+        options.case_folding = CaseFolding::Full;
+
+        let results = FuzzySearchGraphemes::find("strasse", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(2, results.len());
+        assert_eq!("Straße", results[0].match_text);
+        assert_eq!((4, 10), (results[0].start_index, results[0].end_index));
+        assert_eq!((4, 11), (results[0].start_byte, results[0].end_byte));
+        assert_eq!("STRASSE", results[1].match_text);
+
+        let results = FuzzySearchGraphemes::find("straße", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(2, results.len());
+    }
+
+    #[test]
+    fn test_many_distinct_clusters() {
+        // more distinct clusters than there are private use chars
+        let mut text = (0..140_000u32)
+            .map(|i| {
+                [
+                    char::from_u32('a' as u32 + i % 26).unwrap(),
+                    char::from_u32(0x300 + i / 26 % 112).unwrap(),
+                    char::from_u32(0x300 + i / (26 * 112) % 112).unwrap(),
+                ]
+                .iter()
+                .collect::<String>()
+            })
+            .collect::<String>();
+        text.push_str("needle");
+        let options = FuzzySearchOptions::new(1);
+
+        let results = FuzzySearchGraphemes::find("needle", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!("needle", results[0].match_text);
+        assert_eq!(140_000, results[0].start_index);
+    }
+
+    #[test]
+    fn test_same_cluster_matches() {
+        let text = "a🏳️‍🌈b a🏳️‍⚧️b";
+        let options = FuzzySearchOptions::new(0);
+
+        let results = FuzzySearchGraphemes::find("a🏳️‍🌈b", text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!((0, 3), (results[0].start_index, results[0].end_index));
+    }
+
+    #[test]
+    fn test_empty() {
+        let options = FuzzySearchOptions::new(1);
+
+        assert_eq!(0, FuzzySearchGraphemes::find("", "foo", &options).count());
+        assert_eq!(0, FuzzySearchGraphemes::find("foo", "", &options).count());
+    }
+}
@@ -1,29 +1,77 @@
-use crate::{candidate_match::CandidateMatch, fuzzy_search_options::FuzzySearchOptions};
+use std::borrow::Cow;
+
+use crate::{
+    candidate_match::{CandidateMatch, Costs, EDIT_COST},
+    fuzzy_search_options::FuzzySearchOptions,
+    pattern::{Pattern, PatternElement},
+    prefilter::Windows,
+};
 
 pub struct FuzzySearchLevenshtein<'a> {
-    pattern_chars: Vec<char>,
-    text_chars: &'a [char],
+    pattern: Vec<PatternElement>,
+    text_chars: Cow<'a, [char]>,
     options: &'a FuzzySearchOptions,
     candidates: Vec<CandidateMatch>,
+    visited: VisitedStates,
+    edit_costs: Option<Cow<'a, [u32]>>, // cost of edits at each text position, full cost if None
     current_text_index: usize,
-    best_found_distance: usize,
+    best_found_cost: u32,
+    windows: Option<Windows>, // match start indexes worth verifying, all if None
 }
 
 impl<'a> FuzzySearchLevenshtein<'a> {
-    pub fn find(pattern: &str, text_chars: &'a [char], options: &'a FuzzySearchOptions) -> Self {
+    /// The text is expected to be normalized already, the pattern is normalized here.
+    /// If edit costs are given, substitutions and insertions cost the given amount at each text position, see [`EDIT_COST`]
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        edit_costs: Option<Cow<'a, [u32]>>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        // the prepared pattern can be longer than the pattern, eg ß is folded to ss
+        let pattern = pattern.prepare(options).elements().to_vec();
+
         Self {
+            edit_costs,
             options,
-            pattern_chars: pattern.chars().collect(),
             candidates: vec![CandidateMatch::new(0, 0)],
-            text_chars,
-            current_text_index: if pattern.len() == 0 {
+            visited: VisitedStates::new(pattern.len()),
+            current_text_index: if pattern.is_empty() {
                 // this is basically here to eagerly terminate stuff if pattern is an empty string without checking in the next function
                 text_chars.len() + 1
             } else {
                 0
             },
-            best_found_distance: options.max_total_distance,
+            best_found_cost: options.max_total_cost(),
+            windows: None,
+            pattern,
+            text_chars,
+        }
+    }
+
+    /// Only starts matches at indexes inside the windows
+    pub fn with_windows(mut self, windows: Option<Windows>) -> Self {
+        self.windows = windows;
+
+        if self.windows.is_some() && self.current_text_index < self.text_chars.len() {
+            self.current_text_index = self.next_start(0);
+            self.candidates = vec![CandidateMatch::new(
+                self.current_text_index,
+                self.current_text_index,
+            )];
+        }
+
+        self
+    }
+
+    /// Continues after an exact match starting at the index before the windows,
+    /// so the first start only looks for exact matches unless a start was skipped in between
+    pub fn after_exact_match(mut self, start_index: usize) -> Self {
+        if !self.skips_allowed_start(start_index + 1) {
+            self.best_found_cost = 0;
         }
+
+        self
     }
 
     #[inline(always)]
@@ -31,21 +79,83 @@ impl<'a> FuzzySearchLevenshtein<'a> {
         candidates: &mut Vec<CandidateMatch>,
         candidate: &CandidateMatch,
         text: &[char],
-        pattern: &[char],
-        best_found_distance: usize,
+        pattern: &[PatternElement],
+        best_found_cost: u32,
         options: &FuzzySearchOptions,
-        text_length: usize,
+        edit_costs: Option<&[u32]>,
     ) {
-        if candidate.text_index < text_length
-            && text[candidate.text_index] == pattern[candidate.pattern_index]
+        let element = &pattern[candidate.pattern_index];
+        let text_length = text.len();
+        let edit_cost = |text_index: usize| {
+            edit_costs
+                .and_then(|c| c.get(text_index).copied())
+                .unwrap_or(EDIT_COST)
+        };
+
+        // every step except free skips advances the pattern, so the history is shifted by one for each step
+        let match_history = candidate.edit_history << 1;
+        let edit_history = match_history | 1;
+        let can_edit =
+            candidate.costs.total < best_found_cost && options.allows_edit_history(edit_history);
+
+        if options.layout_tolerant {
+            Self::skip_optional_spaces(candidates, candidate, text, pattern);
+        }
+
+        if let Some(ignorable_chars) = &options.ignorable_chars {
+            if candidate.text_index > candidate.start_index
+                && candidate.text_index < text_length
+                && ignorable_chars.contains(text[candidate.text_index])
+            {
+                candidates.push(CandidateMatch {
+                    ignored: candidate.ignored + 1,
+                    text_index: candidate.text_index + 1,
+                    ..*candidate
+                });
+            }
+        }
+
+        if candidate.pattern_index == 0
+            && options.is_anchored_start()
+            && candidate.text_index < text_length
+            && can_edit
+            && options.can_insert_cost(&candidate.costs, edit_cost(candidate.text_index))
         {
-            if candidate.distance < best_found_distance
-                && options.can_insert(candidate.distance, candidate.insertions)
+            // matches anchored to the start of the text can begin with extra chars
+            candidates.push(CandidateMatch {
+                insertions: candidate.insertions + 1,
+                distance: candidate.distance + 1,
+                costs: candidate.costs.insert(edit_cost(candidate.text_index)),
+                edit_history,
+                text_index: candidate.text_index + 1,
+                ..*candidate
+            });
+        }
+
+        if let PatternElement::Gap { min, max } = element {
+            // skip over the gap, longest first so shorter gaps are explored first
+            for gap in (*min..=*max).rev() {
+                if candidate.text_index + gap <= text_length {
+                    candidates.push(CandidateMatch {
+                        text_index: candidate.text_index + gap,
+                        pattern_index: candidate.pattern_index + 1,
+                        edit_history: match_history,
+                        ..*candidate
+                    });
+                }
+            }
+        } else if candidate.text_index < text_length
+            && element.matches_with(text[candidate.text_index], options.char_matcher.as_deref())
+        {
+            if can_edit
+                && options.can_insert_cost(&candidate.costs, edit_cost(candidate.text_index + 1))
             {
                 // jump over one character in text
                 candidates.push(CandidateMatch {
                     insertions: candidate.insertions + 1,
                     distance: candidate.distance + 1,
+                    costs: candidate.costs.insert(edit_cost(candidate.text_index + 1)),
+                    edit_history,
                     pattern_index: candidate.pattern_index + 1,
                     text_index: candidate.text_index + 2,
                     ..*candidate
@@ -56,24 +166,30 @@ impl<'a> FuzzySearchLevenshtein<'a> {
             candidates.push(CandidateMatch {
                 text_index: candidate.text_index + 1,
                 pattern_index: candidate.pattern_index + 1,
+                edit_history: match_history,
                 ..*candidate
             });
-        } else if candidate.distance < best_found_distance {
-            if options.can_delete(candidate.distance, candidate.deletions) {
+        } else if can_edit {
+            // deletions have no position in the text, so they always have full cost
+            if options.can_delete_cost(&candidate.costs, EDIT_COST) {
                 // jump over one character in pattern
                 candidates.push(CandidateMatch {
                     deletions: candidate.deletions + 1,
                     distance: candidate.distance + 1,
+                    costs: candidate.costs.delete(EDIT_COST),
+                    edit_history,
                     pattern_index: candidate.pattern_index + 1,
                     ..*candidate
                 });
             }
 
-            if options.can_substitute(candidate.distance, candidate.substitutions) {
+            if options.can_substitute_cost(&candidate.costs, edit_cost(candidate.text_index)) {
                 // substitute one character
                 candidates.push(CandidateMatch {
                     substitutions: candidate.substitutions + 1,
                     distance: candidate.distance + 1,
+                    costs: candidate.costs.substitute(edit_cost(candidate.text_index)),
+                    edit_history,
                     text_index: candidate.text_index + 1,
                     pattern_index: candidate.pattern_index + 1,
                     ..*candidate
@@ -81,6 +197,138 @@ impl<'a> FuzzySearchLevenshtein<'a> {
             }
         }
     }
+
+    /// Next index from which a match may start, skipping starts in the middle of words and outside the windows if required
+    #[inline(always)]
+    fn next_start(&mut self, mut index: usize) -> usize {
+        let text_length = self.text_chars.len();
+
+        loop {
+            if let Some(windows) = &mut self.windows {
+                index = windows.next_index(index).unwrap_or(text_length);
+            }
+
+            if index >= text_length || self.options.allows_match_start(&self.text_chars, index) {
+                return index.min(text_length);
+            }
+
+            index += 1;
+        }
+    }
+
+    /// Whether the windows skipped an allowed match start between the index and the current start
+    fn skips_allowed_start(&self, index: usize) -> bool {
+        self.windows.is_some()
+            && (index..self.current_text_index)
+                .any(|i| self.options.allows_match_start(&self.text_chars, i))
+    }
+
+    /// Spaces between letters in either the text or the pattern can be skipped at no cost
+    #[inline(always)]
+    fn skip_optional_spaces(
+        candidates: &mut Vec<CandidateMatch>,
+        candidate: &CandidateMatch,
+        text: &[char],
+        pattern: &[PatternElement],
+    ) {
+        let text_length = text.len();
+        let is_letter = |e: Option<&PatternElement>| matches!(e, Some(PatternElement::Char(c)) if c.is_alphanumeric());
+        let pattern_index = candidate.pattern_index;
+
+        if pattern[pattern_index] == PatternElement::Char(' ')
+            && pattern_index > 0
+            && is_letter(pattern.get(pattern_index - 1))
+            && is_letter(pattern.get(pattern_index + 1))
+        {
+            candidates.push(CandidateMatch {
+                pattern_index: pattern_index + 1,
+                edit_history: candidate.edit_history << 1,
+                ..*candidate
+            });
+        }
+
+        let text_index = candidate.text_index;
+
+        if text_index > candidate.start_index
+            && text_index + 1 < text_length
+            && text[text_index] == ' '
+            && text[text_index - 1].is_alphanumeric()
+            && text[text_index + 1].is_alphanumeric()
+        {
+            candidates.push(CandidateMatch {
+                text_index: text_index + 1,
+                ..*candidate
+            });
+        }
+    }
+}
+
+/// Best state seen for each text and pattern index since the current start index.
+/// Candidates reaching a state which was already explored with no more cost and no more recent edits cannot find anything new
+struct VisitedStates {
+    columns: usize,
+    states: Vec<VisitedState>, // indexed by text index relative to the start, then pattern index
+}
+
+#[derive(Clone, Copy)]
+struct VisitedState {
+    start_index: usize, // states from earlier start indexes are stale
+    costs: Costs,
+    edit_history: u64,
+    ignored: usize, // states which skipped a different number of ignorable chars report different matches
+}
+
+impl VisitedStates {
+    fn new(pattern_length: usize) -> Self {
+        Self {
+            columns: pattern_length + 1,
+            states: Vec::new(),
+        }
+    }
+
+    /// Records the state of the candidate, returns false if it is dominated by an already visited state
+    #[inline(always)]
+    fn visit(&mut self, candidate: &CandidateMatch) -> bool {
+        debug_assert!(candidate.pattern_index < self.columns);
+
+        let index =
+            (candidate.text_index - candidate.start_index) * self.columns + candidate.pattern_index;
+
+        if index >= self.states.len() {
+            self.states.resize(
+                index + self.columns,
+                VisitedState {
+                    start_index: usize::MAX,
+                    costs: Costs::default(),
+                    edit_history: 0,
+                    ignored: 0,
+                },
+            );
+        }
+
+        let state = &mut self.states[index];
+        let costs = &candidate.costs;
+
+        if state.start_index == candidate.start_index
+            && state.costs.total <= costs.total
+            && state.costs.substitutions <= costs.substitutions
+            && state.costs.deletions <= costs.deletions
+            && state.costs.insertions <= costs.insertions
+            && state.edit_history & !candidate.edit_history == 0
+            && state.ignored == candidate.ignored
+        {
+            return false;
+        }
+
+        *state = VisitedState {
+            start_index: candidate.start_index,
+            costs: candidate.costs,
+            edit_history: candidate.edit_history,
+            ignored: candidate.ignored,
+        };
+
+        true
+    }
 }
 
 impl<'a> Iterator for FuzzySearchLevenshtein<'a> {
@@ -89,39 +337,49 @@ impl<'a> Iterator for FuzzySearchLevenshtein<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         while self.current_text_index < self.text_chars.len() {
             while let Some(candidate) = self.candidates.pop() {
-                if candidate.pattern_index == self.pattern_chars.len() {
-                    if candidate.text_index <= self.text_chars.len() {
+                if candidate.pattern_index == self.pattern.len() {
+                    if candidate.text_index > self.text_chars.len() {
+                        if candidate.distance == 0 {
+                            self.candidates.clear();
+                        }
+                    } else if self
+                        .options
+                        .allows_match_end(&self.text_chars, candidate.text_index)
+                    {
+                        self.best_found_cost = candidate.costs.total;
+
                         if candidate.distance == 0 {
+                            let exact_start = self.current_text_index;
                             self.candidates.clear();
-                            self.current_text_index += 1;
+                            self.current_text_index = self.next_start(exact_start + 1);
                             self.candidates.push(CandidateMatch::new(
                                 self.current_text_index,
                                 self.current_text_index,
                             ));
+
+                            // the skipped starts have no matches, so the next one is searched like after them
+                            if self.skips_allowed_start(exact_start + 1) {
+                                self.best_found_cost = self.options.max_total_cost();
+                            }
                         }
 
-                        self.best_found_distance = candidate.distance;
                         return Some(candidate);
                     }
-
-                    if candidate.distance == 0 {
-                        self.candidates.clear();
-                    }
-                } else {
+                } else if self.visited.visit(&candidate) {
                     Self::handle_candidate(
                         &mut self.candidates,
                         &candidate,
                         &self.text_chars,
-                        &self.pattern_chars,
-                        self.best_found_distance,
+                        &self.pattern,
+                        self.best_found_cost,
                         self.options,
-                        self.text_chars.len(),
+                        self.edit_costs.as_deref(),
                     );
                 }
             }
 
-            self.current_text_index += 1;
-            self.best_found_distance = self.options.max_total_distance;
+            self.current_text_index = self.next_start(self.current_text_index + 1);
+            self.best_found_cost = self.options.max_total_cost();
             self.candidates.push(CandidateMatch::new(
                 self.current_text_index,
                 self.current_text_index,
@@ -134,9 +392,11 @@ impl<'a> Iterator for FuzzySearchLevenshtein<'a> {
 
 #[cfg(test)]
 mod fuzzy_search_levenshtein_tests {
-    use crate::{
-        fuzzy_search_levenshtein::FuzzySearchLevenshtein, fuzzy_search_options::FuzzySearchOptions,
-    };
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::char_class::CharClass;
+    use crate::fuzzy_search_options::CaseFolding;
 
     #[test]
     fn test_all_results() {
@@ -146,11 +406,97 @@ mod fuzzy_search_levenshtein_tests {
     fn run_find_levenshtein_all(pattern: &str, text: &str, max_distance: usize) {
         let options = FuzzySearchOptions::new(max_distance);
         let text = text.chars().collect::<Vec<_>>();
-        let all_results =
-            FuzzySearchLevenshtein::find(pattern, &text, &options).collect::<Vec<_>>();
+        let all_results = FuzzySearchLevenshtein::find(
+            &Pattern::literal(pattern),
+            Cow::Borrowed(&text),
+            None,
+            &options,
+        )
+        .collect::<Vec<_>>();
 
         println!("{all_results:?}");
 
-        assert_eq!(all_results.len(), 24);
+        // duplicate paths to the same states are pruned
+        assert_eq!(all_results.len(), 20);
+    }
+
+    #[test]
+    fn test_prepared_pattern_longer_than_pattern() {
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+        let text = "maßstäbe masssstäbe massstabe".chars().collect::<Vec<_>>();
+        let find = |pattern: &str| {
+            FuzzySearchLevenshtein::find(
+                &Pattern::literal(pattern),
+                Cow::Borrowed(&text),
+                None,
+                &options,
+            )
+            .map(|c| (c.start_index, c.text_index, c.distance))
+            .collect::<Vec<_>>()
+        };
+
+        // the text is folded by the caller, so only the pattern differs
+        assert_eq!(find("masssstäbe"), find("maßßtäbe"));
+    }
+
+    #[test]
+    fn test_visited_states() {
+        let mut visited = VisitedStates::new(7);
+        let candidate = CandidateMatch {
+            pattern_index: 3,
+            distance: 1,
+            costs: Costs::default().substitute(EDIT_COST),
+            edit_history: 0b10,
+            ..CandidateMatch::new(2, 5)
+        };
+
+        assert!(visited.visit(&candidate));
+        assert!(!visited.visit(&candidate));
+        assert!(!visited.visit(&CandidateMatch {
+            distance: 2,
+            costs: candidate.costs.insert(EDIT_COST),
+            edit_history: 0b11,
+            ..candidate
+        }));
+
+        // different edits, cheaper or a later start are not dominated
+        assert!(visited.visit(&CandidateMatch {
+            costs: Costs::default().delete(EDIT_COST),
+            ..candidate
+        }));
+        assert!(visited.visit(&CandidateMatch {
+            distance: 0,
+            costs: Costs::default(),
+            edit_history: 0,
+            ..candidate
+        }));
+        assert!(visited.visit(&CandidateMatch {
+            start_index: 3,
+            ..candidate
+        }));
+        assert!(visited.visit(&CandidateMatch {
+            ignored: 1,
+            ..candidate
+        }));
+    }
+
+    #[test]
+    fn test_visited_states_with_ignorable_chars() {
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignorable_chars = Some(CharClass::from_chars("."));
+        let text = "a.b.c a..bc".chars().collect::<Vec<_>>();
+
+        let results = FuzzySearchLevenshtein::find(
+            &Pattern::literal("abc"),
+            Cow::Borrowed(&text),
+            None,
+            &options,
+        )
+        .filter(|c| c.distance == 0)
+        .map(|c| (c.start_index, c.text_index, c.ignored))
+        .collect::<Vec<_>>();
+
+        assert_eq!(results, vec![(0, 5, 2), (6, 11, 2)]);
     }
 }
@@ -0,0 +1,284 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    ops::Range,
+    simd::{
+        prelude::{SimdInt, SimdOrd, SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+};
+
+use crate::{
+    fuzzy_search_options::FuzzySearchOptions,
+    pattern::{Pattern, PatternElement},
+    prefilter::Windows,
+};
+
+const LANES: usize = 16;
+
+// text chars of lanes which ran past their segment, never equal to a pattern char
+const NO_CHAR: u32 = u32::MAX;
+
+/// Part of the text computed by one lane, matches ending in (emit_from, emit_to] are reported
+struct Segment {
+    scan_from: usize,
+    emit_from: usize,
+    emit_to: usize,
+}
+
+/// Levenshtein search with the text split into segments which are computed side by side in simd lanes.
+/// Each lane runs the plain dp over its segment, starting early enough to cover the longest match ending in it.
+/// End positions within the distance are collected first, the banded dp then traces the matches ending there back to their starts
+pub struct FuzzySearchLevenshteinSimd<'a> {
+    pattern: Vec<PatternElement>,
+    text_chars: Cow<'a, [char]>,
+    options: &'a FuzzySearchOptions,
+    windows: Option<Windows>,      // text ranges worth scanning, all if None
+    hits: Option<VecDeque<usize>>, // end indexes, computed on the first call to next
+}
+
+impl<'a> FuzzySearchLevenshteinSimd<'a> {
+    /// The text is expected to be normalized already, the pattern is normalized here
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        Self {
+            pattern: pattern.prepare(options).elements().to_vec(),
+            text_chars,
+            options,
+            windows: None,
+            hits: None,
+        }
+    }
+
+    /// Only scans the text inside the windows, the windows should cover the whole text range of the matches
+    pub fn with_windows(mut self, windows: Option<Windows>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Splits the scanned text into segments of similar length, overlapping by the longest possible match
+    fn segments(&self) -> Vec<Segment> {
+        let text_length = self.text_chars.len();
+        let regions: Vec<Range<usize>> = match &self.windows {
+            Some(windows) => windows
+                .ranges()
+                .iter()
+                .map(|r| r.start..r.end.min(text_length))
+                .filter(|r| !r.is_empty())
+                .collect(),
+            None => std::iter::once(0..text_length).collect(),
+        };
+
+        let longest_match = self.pattern.len() + self.options.max_total_distance;
+        let total = regions.iter().map(|r| r.len()).sum::<usize>();
+        let segment_length = total.div_ceil(LANES).max(4 * longest_match).max(1);
+
+        regions
+            .into_iter()
+            .flat_map(|region| {
+                region
+                    .clone()
+                    .step_by(segment_length)
+                    .map(move |from| Segment {
+                        scan_from: from.saturating_sub(longest_match).max(region.start),
+                        emit_from: from,
+                        emit_to: (from + segment_length).min(region.end),
+                    })
+            })
+            .collect()
+    }
+
+    /// Lanes where the pattern element matches the text char
+    #[inline(always)]
+    fn element_mask(&self, element: &PatternElement, chars: Simd<u32, LANES>) -> Mask<i32, LANES> {
+        match (element, &self.options.char_matcher) {
+            (PatternElement::Char(c), None) => chars.simd_eq(Simd::splat(*c as u32)),
+            _ => {
+                // anything but plain chars is checked lane by lane
+                let chars = chars.to_array();
+                Mask::from_array(std::array::from_fn(|i| {
+                    char::from_u32(chars[i]).is_some_and(|c| {
+                        element.matches_with(c, self.options.char_matcher.as_deref())
+                    })
+                }))
+            }
+        }
+    }
+
+    /// Runs the dp for up to LANES segments at once, returns the end indexes within the limit
+    fn scan(&self, segments: &[Segment]) -> Vec<usize> {
+        let max_distance = self.options.max_total_distance;
+        let cap = Simd::splat(max_distance as u32 + 1);
+        let one = Simd::splat(1);
+
+        let steps = segments
+            .iter()
+            .map(|s| s.emit_to - s.scan_from)
+            .max()
+            .unwrap_or(0);
+        let scan_from: [usize; LANES] =
+            std::array::from_fn(|l| segments.get(l).map_or(0, |s| s.scan_from));
+
+        // the first row is free anywhere in the text, anchored matches pay for leading text chars with insertions
+        let anchored = self.options.is_anchored_start();
+        let first_row = |step: usize| match anchored {
+            true => Simd::from_array(scan_from.map(|from| (from + step) as u32)).simd_min(cap),
+            false => Simd::splat(0),
+        };
+
+        // leading pattern chars are deleted before the first text char
+        let mut column = (0..=self.pattern.len())
+            .map(|i| (first_row(0) + Simd::splat(i as u32)).simd_min(cap))
+            .collect::<Vec<Simd<u32, LANES>>>();
+
+        let mut hits = Vec::new();
+
+        for step in 0..steps {
+            let chars = Simd::from_array(std::array::from_fn(|l| match segments.get(l) {
+                Some(segment) if segment.scan_from + step < segment.emit_to => {
+                    self.text_chars[segment.scan_from + step] as u32
+                }
+                _ => NO_CHAR,
+            }));
+
+            let mut diagonal = column[0];
+            let mut above = first_row(step + 1);
+            column[0] = above;
+
+            for (i, element) in self.pattern.iter().enumerate() {
+                let left = column[i + 1];
+                let mismatch = (!self.element_mask(element, chars)).to_simd().cast::<u32>() & one;
+
+                above = (diagonal + mismatch)
+                    .simd_min(left + one)
+                    .simd_min(above + one)
+                    .simd_min(cap);
+
+                column[i + 1] = above;
+                diagonal = left;
+            }
+
+            let mut within = above.simd_le(Simd::splat(max_distance as u32)).to_bitmask();
+
+            while within != 0 {
+                let lane = within.trailing_zeros() as usize;
+                within &= within - 1;
+
+                if let Some(segment) = segments.get(lane) {
+                    let end_index = segment.scan_from + step + 1;
+
+                    if end_index > segment.emit_from && end_index <= segment.emit_to {
+                        hits.push(end_index);
+                    }
+                }
+            }
+        }
+
+        hits
+    }
+
+    fn find_hits(&self) -> VecDeque<usize> {
+        if self.pattern.is_empty() {
+            return VecDeque::new();
+        }
+
+        let segments = self.segments();
+        let mut hits = segments
+            .chunks(LANES)
+            .flat_map(|segments| self.scan(segments))
+            .collect::<Vec<_>>();
+
+        hits.sort_unstable();
+        hits.into()
+    }
+}
+
+impl<'a> Iterator for FuzzySearchLevenshteinSimd<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.hits.is_none() {
+            self.hits = Some(self.find_hits());
+        }
+
+        while let Some(end_index) = self.hits.as_mut().and_then(|h| h.pop_front()) {
+            if self.options.allows_match_end(&self.text_chars, end_index) {
+                return Some(end_index);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_levenshtein_simd_tests {
+    use super::*;
+    use crate::{fuzzy_search_myers::FuzzySearchMyers, fuzzy_search_options::SearchMode};
+
+    fn assert_same_as_myers(pattern: &str, text: &str, options: &FuzzySearchOptions) {
+        let pattern = Pattern::literal(pattern);
+        let text = text.chars().collect::<Vec<_>>();
+
+        let expected =
+            FuzzySearchMyers::find(&pattern, Cow::Borrowed(&text), options).collect::<Vec<_>>();
+        let actual = FuzzySearchLevenshteinSimd::find(&pattern, Cow::Borrowed(&text), options)
+            .collect::<Vec<_>>();
+
+        assert!(!expected.is_empty());
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_short_text() {
+        let options = FuzzySearchOptions::new(2);
+        assert_same_as_myers("pattern", "--patern--xpattttern-pattxrn--", &options);
+    }
+
+    #[test]
+    fn test_many_segments() {
+        let options = FuzzySearchOptions::new(3);
+        let text = "lorem ipsum constitutional dolor sit conftitufional amet constittuional elit "
+            .repeat(50);
+
+        assert_same_as_myers("constitutional", &text, &options);
+    }
+
+    #[test]
+    fn test_matches_across_segments() {
+        let options = FuzzySearchOptions::new(2);
+        // segments are at least 4 times the longest match, matches are spread over every offset
+        let text = (0..400)
+            .map(|i| format!("{}patxern", "-".repeat(i % 13)))
+            .collect::<String>();
+
+        assert_same_as_myers("pattern", &text, &options);
+    }
+
+    #[test]
+    fn test_anchored_start() {
+        let mut options = FuzzySearchOptions::new(2);
+        options.search_mode = SearchMode::Prefix;
+
+        assert_same_as_myers("pattern", "xpatern and pattern", &options);
+    }
+
+    #[test]
+    fn test_windows() {
+        let options = FuzzySearchOptions::new(1);
+        let text = "pattern ".repeat(100).chars().collect::<Vec<_>>();
+
+        let ends = FuzzySearchLevenshteinSimd::find(
+            &Pattern::literal("pattern"),
+            Cow::Borrowed(&text),
+            &options,
+        )
+        .with_windows(Some(Windows::new(vec![16..23, 400..420])))
+        .collect::<Vec<_>>();
+
+        assert_eq!(vec![22, 23, 406, 407, 408, 414, 415, 416], ends);
+    }
+}
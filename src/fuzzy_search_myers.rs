@@ -0,0 +1,317 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    fuzzy_search_options::FuzzySearchOptions,
+    pattern::{Pattern, PatternElement},
+    prefilter::{next_window_index, Windows},
+};
+
+const WORD_SIZE: usize = 64;
+
+/// Vertical delta bit vectors of one 64 row block of the dp matrix
+#[derive(Clone)]
+struct Block {
+    positive: u64,
+    negative: u64,
+}
+
+/// Myers bit-parallel levenshtein search with Hyyrö's multi word blocks for patterns longer than 64 chars.
+/// Finds the end positions where the distance is within the limit, the banded dp then traces the matches ending there back to their starts
+pub struct FuzzySearchMyers<'a> {
+    pattern: Vec<PatternElement>,
+    text_chars: Cow<'a, [char]>,
+    options: &'a FuzzySearchOptions,
+    blocks: Vec<Block>,
+    last_row_mask: u64,           // bit of the last pattern row in the last block
+    ascii_peq: Vec<u64>,          // match bit vectors for ascii chars, blocks.len() words per char
+    peq: HashMap<char, Vec<u64>>, // match bit vectors for other chars, built when first seen
+    score: usize,
+    current_text_index: usize,
+    windows: Option<Windows>, // text ranges worth scanning, all if None
+}
+
+impl<'a> FuzzySearchMyers<'a> {
+    /// The text is expected to be normalized already, the pattern is normalized here
+    pub fn find(
+        pattern: &Pattern,
+        text_chars: Cow<'a, [char]>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        let pattern = pattern.prepare(options).elements().to_vec();
+        let block_count = pattern.len().div_ceil(WORD_SIZE);
+
+        let mut search = Self {
+            blocks: vec![
+                Block {
+                    positive: u64::MAX,
+                    negative: 0,
+                };
+                block_count
+            ],
+            last_row_mask: 1 << ((pattern.len() + WORD_SIZE - 1) % WORD_SIZE),
+            ascii_peq: Vec::new(),
+            peq: HashMap::new(),
+            score: pattern.len(),
+            windows: None,
+            current_text_index: if pattern.is_empty() {
+                text_chars.len() + 1
+            } else {
+                0
+            },
+            pattern,
+            text_chars,
+            options,
+        };
+
+        search.ascii_peq = (0..128u8)
+            .flat_map(|c| search.build_peq(c as char))
+            .collect();
+
+        search
+    }
+
+    /// Only scans the text inside the windows, the windows should cover the whole text range of the matches
+    pub fn with_windows(mut self, windows: Option<Windows>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Starts over with an empty matrix at the index
+    fn restart(&mut self, index: usize) {
+        self.blocks.fill(Block {
+            positive: u64::MAX,
+            negative: 0,
+        });
+        self.score = self.pattern.len();
+        self.current_text_index = index;
+    }
+
+    fn build_peq(&self, c: char) -> Vec<u64> {
+        let mut peq = vec![0; self.blocks.len()];
+
+        for (i, element) in self.pattern.iter().enumerate() {
+            if element.matches_with(c, self.options.char_matcher.as_deref()) {
+                peq[i / WORD_SIZE] |= 1 << (i % WORD_SIZE);
+            }
+        }
+
+        peq
+    }
+
+    /// Advances one block by a text column, returns the horizontal delta out of the last row of the block
+    #[inline(always)]
+    fn advance_block(block: &mut Block, eq: u64, horizontal_in: i32, high_bit: u64) -> i32 {
+        let Block { positive, negative } = *block;
+
+        let xv = eq | negative;
+        let eq = match horizontal_in < 0 {
+            true => eq | 1,
+            false => eq,
+        };
+        let xh = (((eq & positive).wrapping_add(positive)) ^ positive) | eq;
+
+        let mut horizontal_positive = negative | !(xh | positive);
+        let mut horizontal_negative = positive & xh;
+
+        let horizontal_out = if horizontal_positive & high_bit != 0 {
+            1
+        } else if horizontal_negative & high_bit != 0 {
+            -1
+        } else {
+            0
+        };
+
+        horizontal_positive <<= 1;
+        horizontal_negative <<= 1;
+
+        match horizontal_in {
+            1.. => horizontal_positive |= 1,
+            ..0 => horizontal_negative |= 1,
+            0 => {}
+        }
+
+        block.positive = horizontal_negative | !(xv | horizontal_positive);
+        block.negative = horizontal_positive & xv;
+
+        horizontal_out
+    }
+
+    /// Advances the whole matrix by the text char at the current index
+    #[inline(always)]
+    fn advance(&mut self, c: char) {
+        if c.is_ascii() {
+            let block_count = self.blocks.len();
+            let offset = c as usize * block_count;
+            Self::advance_blocks(
+                &mut self.blocks,
+                &self.ascii_peq[offset..offset + block_count],
+                self.last_row_mask,
+                self.options.is_anchored_start(),
+                &mut self.score,
+            );
+        } else {
+            if !self.peq.contains_key(&c) {
+                let peq = self.build_peq(c);
+                self.peq.insert(c, peq);
+            }
+
+            Self::advance_blocks(
+                &mut self.blocks,
+                &self.peq[&c],
+                self.last_row_mask,
+                self.options.is_anchored_start(),
+                &mut self.score,
+            );
+        }
+    }
+
+    #[inline(always)]
+    fn advance_blocks(
+        blocks: &mut [Block],
+        peq: &[u64],
+        last_row_mask: u64,
+        anchored_start: bool,
+        score: &mut usize,
+    ) {
+        // the first row costs one per text char when the match must start at the beginning of the text
+        let mut horizontal = anchored_start as i32;
+        let last_block = blocks.len() - 1;
+
+        for (i, block) in blocks.iter_mut().enumerate() {
+            let high_bit = match i == last_block {
+                true => last_row_mask,
+                false => 1 << (WORD_SIZE - 1),
+            };
+
+            horizontal = Self::advance_block(block, peq[i], horizontal, high_bit);
+        }
+
+        *score = score.wrapping_add_signed(horizontal as isize);
+    }
+}
+
+impl<'a> Iterator for FuzzySearchMyers<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let max_distance = self.options.max_total_distance;
+
+        while self.current_text_index < self.text_chars.len() {
+            match next_window_index(&mut self.windows, self.current_text_index) {
+                None => break,
+                Some(index) if index > self.current_text_index => self.restart(index),
+                _ => {}
+            }
+
+            if self.options.is_anchored_start()
+                && self.current_text_index >= self.pattern.len() + max_distance
+            {
+                // anchored matches cannot be longer than this
+                break;
+            }
+
+            self.advance(self.text_chars[self.current_text_index]);
+            self.current_text_index += 1;
+
+            let end_index = self.current_text_index;
+
+            if self.score <= max_distance
+                && self.options.allows_match_end(&self.text_chars, end_index)
+            {
+                return Some(end_index);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_myers_tests {
+    use super::*;
+
+    fn scores(pattern: &str, text: &str, options: &FuzzySearchOptions) -> Vec<usize> {
+        let text = text.chars().collect::<Vec<_>>();
+        let mut search =
+            FuzzySearchMyers::find(&Pattern::literal(pattern), Cow::Borrowed(&text), options);
+
+        text.iter()
+            .map(|c| {
+                search.advance(*c);
+                search.score
+            })
+            .collect()
+    }
+
+    /// Semi global distances computed with the plain dp recurrence
+    fn expected_scores(pattern: &str, text: &str) -> Vec<usize> {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+        let mut column = (0..=pattern.len()).collect::<Vec<_>>();
+
+        text.chars()
+            .map(|c| {
+                let mut previous_diagonal = column[0];
+                column[0] = 0;
+
+                for i in 1..=pattern.len() {
+                    let value = (previous_diagonal + (pattern[i - 1] != c) as usize)
+                        .min(column[i] + 1)
+                        .min(column[i - 1] + 1);
+                    previous_diagonal = column[i];
+                    column[i] = value;
+                }
+
+                column[pattern.len()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scores() {
+        let options = FuzzySearchOptions::new(3);
+        let pattern = "pattern";
+        let text = "---patern--pattttern-pattern-ptn";
+
+        assert_eq!(
+            expected_scores(pattern, text),
+            scores(pattern, text, &options)
+        );
+    }
+
+    #[test]
+    fn test_scores_multiple_blocks() {
+        let options = FuzzySearchOptions::new(3);
+        let pattern = "abcdefghijklmnopqrstuvwxyz".repeat(6);
+        let text = "---".to_string()
+            + &pattern.replace("klm", "kxm").replace("uvw", "uw")
+            + "--"
+            + &pattern[..100];
+
+        assert_eq!(
+            expected_scores(&pattern, &text),
+            scores(&pattern, &text, &options)
+        );
+    }
+
+    #[test]
+    fn test_ends() {
+        let options = FuzzySearchOptions::new(3);
+        let pattern = "abcdefghijklmnopqrstuvwxyz".repeat(6);
+        let text =
+            "---".to_string() + &pattern.replacen("klm", "kxm", 1).replacen("uvw", "uw", 1) + "--";
+
+        let expected = expected_scores(&pattern, &text)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, score)| *score <= 3)
+            .map(|(i, _)| i + 1)
+            .collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+        let ends =
+            FuzzySearchMyers::find(&Pattern::literal(&pattern), Cow::Borrowed(&text), &options)
+                .collect::<Vec<_>>();
+
+        assert!(ends.contains(&(text.len() - 2)));
+        assert_eq!(expected, ends);
+    }
+}
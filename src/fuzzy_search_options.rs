@@ -1,8 +1,162 @@
+use std::{error::Error, fmt, sync::Arc};
+
+use crate::{
+    candidate_match::{Costs, EDIT_COST},
+    candidates::Candidates,
+    char_class::{CharCategory, CharClass},
+    char_matcher::CharMatcher,
+    pattern::Pattern,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alphabet {
+    #[default]
+    Text,
+    /// IUPAC nucleotide codes in the pattern match any base in their set at no cost.
+    /// With `text_ambiguity` codes in the text are expanded as well, so for example N in the text matches any base
+    Dna { text_ambiguity: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseFolding {
+    /// Case sensitive matching
+    #[default]
+    None,
+    /// Case insensitive matching where every char folds to a single char, eg ẞ matches ß
+    Simple,
+    /// Case insensitive matching where a char can fold to multiple chars, eg ß matches ss
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WordBoundary {
+    /// Matches can start and end anywhere
+    #[default]
+    None,
+    /// Matches cannot start in the middle of a word
+    Start,
+    /// Matches cannot end in the middle of a word
+    End,
+    /// Matches cannot start or end in the middle of a word
+    Both,
+}
+
+impl WordBoundary {
+    pub fn at_start(&self) -> bool {
+        matches!(self, WordBoundary::Start | WordBoundary::Both)
+    }
+
+    pub fn at_end(&self) -> bool {
+        matches!(self, WordBoundary::End | WordBoundary::Both)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// The pattern can occur anywhere in the text
+    #[default]
+    Anywhere,
+    /// The text must start with the pattern
+    Prefix,
+    /// The text must end with the pattern
+    Suffix,
+    /// The whole text must match the pattern
+    Whole,
+}
+
+/// Limits how close together edits can be, at most `max_edits` within any `window` consecutive pattern chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditDensity {
+    pub max_edits: usize,
+    /// Window length in pattern chars, at most 64
+    pub window: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Backtracking search over candidate matches, supports all options
+    #[default]
+    CandidateStack,
+    /// Myers bit-parallel levenshtein, runs in O(n * m / 64) regardless of the distance.
+    /// Finds where matches end, the banded dp then traces the matches ending there back to their starts.
+    /// Does not support gaps, layout tolerance, ignorable chars, edit density or weights
+    Myers,
+    /// Column wise dp limited to the band of cells within the distance, with a predictable worst case time.
+    /// Finds the best match of each start and its edits without the candidate stack, and traces back the ends found by the other scanning engines.
+    /// Does not support gaps, layout tolerance, ignorable chars, edit density or weights
+    Banded,
+    /// Wu-Manber bitap with one word per error level, fast for patterns of up to 64 chars and small distances.
+    /// Tracks only substitutions if the options allow no insertions or deletions, and then reads the matches from its states.
+    /// Otherwise the ends it finds are traced back like with myers.
+    /// Does not support gaps, layout tolerance, ignorable chars, edit density or weights
+    Bitap,
+    /// Plain dp over segments of the text computed side by side in simd lanes, the ends it finds are traced back like with myers.
+    /// Does not support gaps, layout tolerance, ignorable chars, edit density or weights
+    Simd,
+    /// Picks an engine based on the pattern and options
+    Auto,
+}
+
+/// The engine selected in the options cannot search with the pattern or options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedEngine {
+    pub engine: Engine,
+    pub feature: &'static str,
+}
+
+impl fmt::Display for UnsupportedEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not supported by the {:?} engine",
+            self.feature, self.engine
+        )
+    }
+}
+
+impl Error for UnsupportedEngine {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prefilter {
+    #[default]
+    None,
+    /// Splits the pattern into max_total_distance + 1 pieces, at least one of which occurs exactly in every match,
+    /// and only verifies matches around their occurrences. Fast for long texts with sparse matches.
+    /// Not used with patterns containing anything but plain chars, or with char matchers, layout tolerance, ignorable chars, weights or anchored search modes
+    ExactSeeds,
+    /// Only verifies text regions sharing enough q-grams with the pattern for a match to fit (the q-gram lemma).
+    /// If q is None it is chosen from the pattern length, distance and the alphabet size of the text.
+    /// Has the same restrictions as ExactSeeds
+    QGrams { q: Option<usize> },
+}
+
 pub struct FuzzySearchOptions {
     pub max_total_distance: usize,
     pub max_substitutions: usize,
     pub max_deletions: usize,
     pub max_insertions: usize,
+    pub alphabet: Alphabet,
+    pub case_folding: CaseFolding,
+    /// Compare base letters only, eg é matches e, regardless of whether it is precomposed or uses a combining mark
+    pub ignore_diacritics: bool,
+    /// Tolerate OCR line breaks and spacing: runs of whitespace count as one space, hyphens followed by a line break are ignored
+    /// and spaces between letters in either the text or the pattern are free. The substitutions only searches only do the normalization
+    pub layout_tolerant: bool,
+    pub search_mode: SearchMode,
+    /// Algorithm used by the levenshtein search
+    pub engine: Engine,
+    /// Skips text regions which cannot contain a match before running the engine
+    pub prefilter: Prefilter,
+    /// Decides which pattern and text chars are equal, plain equality if None
+    pub char_matcher: Option<Arc<dyn CharMatcher>>,
+    /// Text chars which can be skipped at no cost inside a match, eg stray punctuation or zero width chars.
+    /// Skipped chars are reported separately from insertions
+    pub ignorable_chars: Option<CharClass>,
+    /// Keeps long fuzzy matches meaningful by preventing clusters of edits, only supported by the levenshtein search
+    pub edit_density: Option<EditDensity>,
+    pub word_boundary: WordBoundary,
+    /// Chars considered part of a word for word boundaries, defaults to unicode alphanumerics and underscore
+    pub word_chars: CharClass,
 }
 
 impl FuzzySearchOptions {
@@ -12,6 +166,18 @@ impl FuzzySearchOptions {
             max_substitutions: max_total_distance,
             max_deletions: max_total_distance,
             max_insertions: max_total_distance,
+            alphabet: Alphabet::Text,
+            case_folding: CaseFolding::None,
+            ignore_diacritics: false,
+            layout_tolerant: false,
+            search_mode: SearchMode::Anywhere,
+            engine: Engine::CandidateStack,
+            prefilter: Prefilter::None,
+            char_matcher: None,
+            ignorable_chars: None,
+            edit_density: None,
+            word_boundary: WordBoundary::None,
+            word_chars: CharClass::from_category(CharCategory::Word),
         }
     }
 
@@ -26,10 +192,10 @@ impl FuzzySearchOptions {
         let max_insertions = max_insertions.unwrap_or(max_total_distance);
 
         FuzzySearchOptions {
-            max_total_distance,
             max_substitutions,
             max_deletions,
             max_insertions,
+            ..Self::new(max_total_distance)
         }
     }
 
@@ -42,10 +208,113 @@ impl FuzzySearchOptions {
             max_substitutions,
             max_deletions,
             max_insertions,
-            max_total_distance: max_deletions + max_insertions + max_substitutions,
+            ..Self::new(max_deletions + max_insertions + max_substitutions)
         }
     }
 
+    /// The engine which searches for the pattern, with auto resolved.
+    /// Searching with an engine which does not support the pattern or options falls back to the candidate stack
+    ///
+    /// # Errors
+    /// If the selected engine does not support the pattern or options
+    pub fn validate_engine(&self, pattern: &Pattern) -> Result<Engine, UnsupportedEngine> {
+        Candidates::engine(pattern, false, self)
+    }
+
+    /// Distance limit for engines which only do substitutions
+    pub fn max_substitutions_only_distance(&self) -> usize {
+        self.max_total_distance.min(self.max_substitutions)
+    }
+
+    /// Returns true if the index is between two word chars
+    #[inline(always)]
+    pub fn splits_word(&self, text: &[char], index: usize) -> bool {
+        index > 0
+            && index < text.len()
+            && self.word_chars.contains(text[index - 1])
+            && self.word_chars.contains(text[index])
+    }
+
+    /// Returns true if the search mode requires matches to start at the beginning of the text
+    pub fn is_anchored_start(&self) -> bool {
+        matches!(self.search_mode, SearchMode::Prefix | SearchMode::Whole)
+    }
+
+    /// Returns true if the search mode requires matches to end at the end of the text
+    pub fn is_anchored_end(&self) -> bool {
+        matches!(self.search_mode, SearchMode::Suffix | SearchMode::Whole)
+    }
+
+    #[inline(always)]
+    pub fn allows_match_start(&self, text: &[char], index: usize) -> bool {
+        (!self.is_anchored_start() || index == 0)
+            && (!self.word_boundary.at_start() || !self.splits_word(text, index))
+    }
+
+    #[inline(always)]
+    pub fn allows_match_end(&self, text: &[char], index: usize) -> bool {
+        (!self.is_anchored_end() || index == text.len())
+            && (!self.word_boundary.at_end() || !self.splits_word(text, index))
+    }
+
+    /// For fixed length matches, which start indexes respect the search mode and word boundaries. None if there are no constraints
+    pub(crate) fn allowed_match_starts(
+        &self,
+        text: &[char],
+        match_length: usize,
+    ) -> Option<Vec<bool>> {
+        match (self.search_mode, self.word_boundary) {
+            (SearchMode::Anywhere, WordBoundary::None) => None,
+            _ => Some(
+                (0..text.len())
+                    .map(|i| {
+                        self.allows_match_start(text, i)
+                            && self.allows_match_end(text, i + match_length)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Total distance limit as a weighted cost
+    pub(crate) fn max_total_cost(&self) -> u32 {
+        scaled_limit(self.max_total_distance)
+    }
+
+    /// Returns true if the edits in the history are within the density limit
+    #[inline(always)]
+    pub fn allows_edit_history(&self, edit_history: u64) -> bool {
+        match self.edit_density {
+            Some(EditDensity { max_edits, window }) => {
+                let window_mask = match window {
+                    0..64 => (1 << window) - 1,
+                    _ => u64::MAX,
+                };
+
+                (edit_history & window_mask).count_ones() as usize <= max_edits
+            }
+            None => true,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn can_substitute_cost(&self, costs: &Costs, cost: u32) -> bool {
+        fits_limit(costs.substitutions, cost, self.max_substitutions)
+            && fits_limit(costs.total, cost, self.max_total_distance)
+    }
+
+    #[inline(always)]
+    pub(crate) fn can_delete_cost(&self, costs: &Costs, cost: u32) -> bool {
+        fits_limit(costs.deletions, cost, self.max_deletions)
+            && fits_limit(costs.total, cost, self.max_total_distance)
+    }
+
+    #[inline(always)]
+    pub(crate) fn can_insert_cost(&self, costs: &Costs, cost: u32) -> bool {
+        fits_limit(costs.insertions, cost, self.max_insertions)
+            && fits_limit(costs.total, cost, self.max_total_distance)
+    }
+
     #[inline(always)]
     pub fn can_substitute(
         &self,
@@ -67,10 +336,99 @@ impl FuzzySearchOptions {
     }
 }
 
+#[inline(always)]
+fn scaled_limit(limit: usize) -> u32 {
+    u32::try_from(limit)
+        .unwrap_or(u32::MAX)
+        .saturating_mul(EDIT_COST)
+}
+
+#[inline(always)]
+fn fits_limit(current_cost: u32, cost: u32, limit: usize) -> bool {
+    current_cost.saturating_add(cost) <= scaled_limit(limit)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_word_boundaries() {
+        let text = "a department, art_s".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+
+        assert!(options.allows_match_start(&text, 6));
+
+        options.word_boundary = WordBoundary::Start;
+        assert!(!options.allows_match_start(&text, 6));
+        assert!(options.allows_match_start(&text, 2));
+        assert!(options.allows_match_end(&text, 9));
+
+        options.word_boundary = WordBoundary::Both;
+        assert!(!options.allows_match_end(&text, 9));
+        assert!(options.allows_match_end(&text, 12));
+        assert!(!options.allows_match_end(&text, 17));
+
+        options.word_chars = CharClass::from_category(CharCategory::Alphabetic);
+        assert!(options.allows_match_end(&text, 17));
+    }
+
+    #[test]
+    fn test_search_mode() {
+        let text = "art dept".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+
+        options.search_mode = SearchMode::Prefix;
+        assert!(options.allows_match_start(&text, 0));
+        assert!(!options.allows_match_start(&text, 4));
+        assert!(options.allows_match_end(&text, 3));
+
+        options.search_mode = SearchMode::Suffix;
+        assert!(options.allows_match_start(&text, 4));
+        assert!(!options.allows_match_end(&text, 3));
+        assert!(options.allows_match_end(&text, 8));
+
+        options.search_mode = SearchMode::Whole;
+        assert!(!options.allows_match_start(&text, 4));
+        assert!(!options.allows_match_end(&text, 3));
+        assert_eq!(
+            Some(vec![true, false, false, false, false, false, false, false]),
+            options.allowed_match_starts(&text, 8)
+        );
+    }
+
+    #[test]
+    fn test_edit_density() {
+        let mut options = FuzzySearchOptions::new(5);
+        assert!(options.allows_edit_history(0b1111));
+
+        options.edit_density = Some(EditDensity {
+            max_edits: 2,
+            window: 4,
+        });
+        assert!(options.allows_edit_history(0b0101));
+        assert!(options.allows_edit_history(0b1_1000_0011));
+        assert!(!options.allows_edit_history(0b1011));
+
+        options.edit_density = Some(EditDensity {
+            max_edits: 2,
+            window: 100,
+        });
+        assert!(!options.allows_edit_history(1 << 63 | 0b11));
+    }
+
+    #[test]
+    fn test_weighted_limits() {
+        let options = FuzzySearchOptions::with_limits(2, Some(1), None, None);
+        let costs = Costs::default().substitute(EDIT_COST / 2);
+
+        assert!(options.can_substitute_cost(&costs, EDIT_COST / 2));
+        assert!(!options.can_substitute_cost(&costs, EDIT_COST));
+        assert!(options.can_delete_cost(&costs, EDIT_COST));
+        assert!(!options.can_delete_cost(&costs.delete(EDIT_COST), EDIT_COST));
+        assert!(FuzzySearchOptions::new(usize::MAX).can_insert_cost(&costs, EDIT_COST));
+    }
+
     #[test]
     fn test_can_substitute_total_distance() {
         let options = FuzzySearchOptions::new(3);
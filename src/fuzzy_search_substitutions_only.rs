@@ -1,29 +1,86 @@
-use crate::match_result::MatchResult;
+use std::sync::Arc;
+
+use crate::{
+    char_matcher::CharMatcher,
+    fuzzy_search_options::FuzzySearchOptions,
+    match_result::MatchResult,
+    normalized_text::{NormalizedText, OffsetMap},
+    pattern::{Pattern, PatternElement, PatternError},
+};
 
 pub struct FuzzySearchSubstitutionsOnly {
-    pattern_chars: Vec<char>,
+    pattern: Vec<PatternElement>,
     text_chars: Vec<char>,
+    original: Option<(Vec<char>, OffsetMap)>, // set if the text was normalized, used for mapping matches back to the original text
     max_distance: usize,
+    allowed_starts: Option<Vec<bool>>, // set if matches must respect word boundaries
+    char_matcher: Option<Arc<dyn CharMatcher>>,
     current_text_index: usize,
     last_index: usize,
 }
 
 impl FuzzySearchSubstitutionsOnly {
     pub fn find(pattern: &str, text: &str, max_distance: usize) -> Self {
+        Self::search(
+            &Pattern::literal(pattern),
+            text,
+            &FuzzySearchOptions::new(max_distance),
+        )
+    }
+
+    /// Search using a parsed pattern. Wildcards are supported, but gaps are not since they change the match length
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_pattern(
+        pattern: &Pattern,
+        text: &str,
+        max_distance: usize,
+    ) -> Result<Self, PatternError> {
+        Self::find_with_options(pattern, text, &FuzzySearchOptions::new(max_distance))
+    }
+
+    /// Search using options, the distance is limited by both max_total_distance and max_substitutions
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_with_options(
+        pattern: &Pattern,
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Result<Self, PatternError> {
+        if pattern.has_gaps() {
+            return Err(PatternError::GapsNotSupported);
+        }
+
+        Ok(Self::search(pattern, text, options))
+    }
+
+    /// Search with a pattern without gaps
+    pub(crate) fn search(pattern: &Pattern, text: &str, options: &FuzzySearchOptions) -> Self {
+        let pattern = pattern.prepare(options);
         let text_chars: Vec<_> = text.chars().collect();
+
+        let (text_chars, original) = match NormalizedText::normalize(&text_chars, options) {
+            Some(normalized) => (normalized.chars, Some((text_chars, normalized.offsets))),
+            None => (text_chars, None),
+        };
+
+        let allowed_starts = options.allowed_match_starts(&text_chars, pattern.len());
         let length = text_chars.len();
-        let pattern_chars: Vec<_> = pattern.chars().collect();
-        let last_index = text_chars
-            .len()
-            .checked_sub(pattern_chars.len())
-            .unwrap_or(0)
-            + 1;
+        let last_index = match text_chars.len() >= pattern.len() {
+            true => text_chars.len() - pattern.len() + 1,
+            false => 0, // pattern longer than the text can never match
+        };
 
         Self {
-            pattern_chars,
-            max_distance,
+            pattern: pattern.elements().to_vec(),
+            max_distance: options.max_substitutions_only_distance(),
+            allowed_starts,
+            char_matcher: options.char_matcher.clone(),
             text_chars,
-            current_text_index: if pattern.len() == 0 || text.len() == 0 {
+            original,
+            current_text_index: if pattern.is_empty() || text.is_empty() {
                 // this is basically here to eagerly terminate stuff if pattern is an empty string without checking in the next function
                 length + 1
             } else {
@@ -42,11 +99,15 @@ impl Iterator for FuzzySearchSubstitutionsOnly {
             let current_index = self.current_text_index;
             self.current_text_index += 1;
 
-            let m = &self.text_chars[current_index..current_index + self.pattern_chars.len()]
+            if !is_allowed_start(&self.allowed_starts, current_index) {
+                continue;
+            }
+
+            let m = &self.text_chars[current_index..current_index + self.pattern.len()]
                 .iter()
-                .zip(&self.pattern_chars)
+                .zip(&self.pattern)
                 .try_fold(0, |a, v| {
-                    let distance = match v.0 == v.1 {
+                    let distance = match v.1.matches_with(*v.0, self.char_matcher.as_deref()) {
                         true => a,
                         false => a + 1,
                     };
@@ -58,16 +119,27 @@ impl Iterator for FuzzySearchSubstitutionsOnly {
                 });
 
             if let Some(distance) = m {
+                let end_index = current_index + self.pattern.len();
+
+                let (start_index, end_index, text_chars) = match &self.original {
+                    Some((original_chars, offsets)) => {
+                        let (start, end) = offsets.original_range(current_index, end_index);
+                        (start, end, original_chars)
+                    }
+                    None => (current_index, end_index, &self.text_chars),
+                };
+
                 return Some(MatchResult {
-                    start_index: current_index,
-                    end_index: current_index + self.pattern_chars.len(),
+                    start_index,
+                    end_index,
                     distance: *distance,
-                    match_text: self.text_chars
-                        [current_index..(current_index + self.pattern_chars.len())]
+                    weighted_distance: *distance as f32,
+                    match_text: text_chars[start_index..end_index]
                         .iter()
                         .collect::<String>(),
                     deletions: 0,
                     insertions: 0,
+                    ignored: 0,
                     substitutions: *distance,
                 });
             }
@@ -77,9 +149,15 @@ impl Iterator for FuzzySearchSubstitutionsOnly {
     }
 }
 
+#[inline(always)]
+pub(crate) fn is_allowed_start(allowed_starts: &Option<Vec<bool>>, index: usize) -> bool {
+    allowed_starts.as_ref().is_none_or(|a| a[index])
+}
+
 #[cfg(test)]
 mod fuzzy_search_substitution_only_tests {
     use super::*;
+    use crate::fuzzy_search_options::SearchMode;
 
     fn assert_match(start_index: usize, end_index: usize, text: &str, m: &MatchResult) {
         assert_eq!(start_index, m.start_index);
@@ -170,6 +248,186 @@ mod fuzzy_search_substitution_only_tests {
         }
     }
 
+    #[test]
+    fn wildcard_pattern() {
+        let pattern = Pattern::parse("INV-????-2023").unwrap();
+        let text = "--INV-0042-2023--INV-0043-2O23--";
+
+        let matches = FuzzySearchSubstitutionsOnly::find_pattern(&pattern, text, 1)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(2, matches.len());
+        assert_match(2, 15, text, &matches[0]);
+        assert_eq!(0, matches[0].distance);
+        assert_match(17, 30, text, &matches[1]);
+        assert_eq!(1, matches[1].distance);
+    }
+
+    #[test]
+    fn class_pattern() {
+        let pattern = Pattern::parse(r"[^aeiou]\s\p{N}").unwrap();
+        let text = "a 1b 2e٣";
+
+        let matches = FuzzySearchSubstitutionsOnly::find_pattern(&pattern, text, 0)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(1, matches.len());
+        assert_match(3, 6, text, &matches[0]);
+    }
+
+    #[test]
+    fn dna_pattern() {
+        let text = "gatcgatcgattagcatagctagcaaacgatcg";
+        let mut options = FuzzySearchOptions::with_limits(2, Some(1), None, None);
+        options.alphabet = crate::fuzzy_search_options::Alphabet::Dna {
+            text_ambiguity: false,
+        };
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("TTRGCWTNGG"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(1, matches.len());
+        assert_match(10, 20, text, &matches[0]);
+        assert_eq!(1, matches[0].distance);
+    }
+
+    #[test]
+    fn case_folding() {
+        let text = "Die STRASSE und die Straße";
+        let mut options = FuzzySearchOptions::new(0);
+        options.case_folding = crate::fuzzy_search_options::CaseFolding::Full;
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("straße"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(2, matches.len());
+        assert_eq!((4, 11), (matches[0].start_index, matches[0].end_index));
+        assert_eq!("STRASSE", matches[0].match_text);
+        assert_eq!((20, 26), (matches[1].start_index, matches[1].end_index));
+        assert_eq!("Straße", matches[1].match_text);
+
+        options.case_folding = crate::fuzzy_search_options::CaseFolding::Simple;
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("straße"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(1, matches.len());
+        assert_eq!("Straße", matches[0].match_text);
+    }
+
+    #[test]
+    fn word_boundary() {
+        let text = "art department, arts and smart art.";
+        let mut options = FuzzySearchOptions::new(1);
+        options.word_boundary = crate::fuzzy_search_options::WordBoundary::Both;
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("art"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(2, matches.len());
+        assert_match(0, 3, text, &matches[0]);
+        assert_match(31, 34, text, &matches[1]);
+
+        options.word_boundary = crate::fuzzy_search_options::WordBoundary::Start;
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("art"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(3, matches.len());
+        assert_match(16, 19, text, &matches[1]);
+        assert_match(31, 34, text, &matches[2]);
+    }
+
+    #[test]
+    fn search_mode() {
+        let mut options = FuzzySearchOptions::new(1);
+        let find = |text: &str, options: &FuzzySearchOptions| {
+            FuzzySearchSubstitutionsOnly::find_with_options(
+                &Pattern::literal("2023"),
+                text,
+                options,
+            )
+            .unwrap()
+            .map(|m| m.start_index)
+            .collect::<Vec<_>>()
+        };
+
+        options.search_mode = SearchMode::Prefix;
+        assert_eq!(vec![0], find("2O23-2023-2023", &options));
+
+        options.search_mode = SearchMode::Suffix;
+        assert_eq!(vec![10], find("2023-2023-2024", &options));
+
+        options.search_mode = SearchMode::Whole;
+        assert_eq!(vec![0], find("2O23", &options));
+        assert!(find("2023-", &options).is_empty());
+        assert!(find("202", &options).is_empty());
+    }
+
+    #[test]
+    fn char_matcher() {
+        let text = "INV-OO42-2O23 INV-0042-2023 INV-0043-2023";
+        let mut options = FuzzySearchOptions::new(0);
+        options.char_matcher = Some(Arc::new(
+            crate::char_matcher::EquivalenceMatcher::look_alikes(),
+        ));
+
+        let matches = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("INV-0042-2023"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(2, matches.len());
+        assert_match(0, 13, text, &matches[0]);
+        assert_match(14, 27, text, &matches[1]);
+    }
+
+    #[test]
+    fn pattern_longer_than_text() {
+        assert_eq!(
+            0,
+            FuzzySearchSubstitutionsOnly::find("foo", "fo", 1).count()
+        );
+    }
+
+    #[test]
+    fn gap_pattern() {
+        let pattern = Pattern::parse("Dear *{0,10},").unwrap();
+        let search = FuzzySearchSubstitutionsOnly::find_pattern(&pattern, "Dear John,", 1);
+
+        assert!(matches!(search, Err(PatternError::GapsNotSupported)));
+    }
+
     #[test]
     fn no_match() {
         let pattern = "foo";
@@ -0,0 +1,563 @@
+use std::simd::{
+    prelude::{SimdInt, SimdPartialEq, SimdPartialOrd, SimdUint},
+    Simd,
+};
+
+use crate::{
+    fuzzy_search_options::{Alphabet, FuzzySearchOptions},
+    fuzzy_search_substitutions_only::{is_allowed_start, FuzzySearchSubstitutionsOnly},
+    match_result::MatchResult,
+    normalized_text::{NormalizedText, OffsetMap},
+    nucleotide,
+    pattern::{Pattern, PatternElement, PatternError},
+    simd_level::SimdLevel,
+};
+
+/// Pattern element compared in byte lanes, wildcards are left out since they never add distance
+#[derive(Clone, Copy)]
+enum ByteElement {
+    Char { offset: usize, byte: u8 },
+    Nucleotides { offset: usize, mask: u8 },
+}
+
+/// Substitutions only search with the text as bytes, so 64 positions fit in a single 512 bit register.
+/// Mismatch counts are accumulated in byte lanes, and the lanes within the distance are found from a bitmask instead of checking each lane.
+/// Chars outside latin 1 never match a pattern char, so they are all mapped to a byte which is not in the pattern.
+/// Patterns with classes, custom char matchers or distances which do not fit in a byte are searched with [`FuzzySearchSubstitutionsOnly`] instead
+pub struct FuzzySearchSubstitutionsOnlyBytes {
+    elements: Vec<ByteElement>,
+    pattern_length: usize,
+    text_bytes: Vec<u8>,
+    text_masks: Vec<u8>, // nucleotide bitmasks of the text chars, only used with the dna alphabet
+    text_chars: Vec<char>,
+    original: Option<(Vec<char>, OffsetMap)>, // set if the text was normalized, used for mapping matches back to the original text
+    max_distance: usize,
+    allowed_starts: Option<Vec<bool>>, // set if matches must respect word boundaries
+    current_text_index: usize,
+    last_index: usize,
+    simd_level: SimdLevel,
+    block_index: usize,
+    block_hits: u64, // lanes of the current block within the distance, not yet returned
+    block_distances: [u8; 64],
+    fallback: Option<FuzzySearchSubstitutionsOnly>,
+}
+
+impl FuzzySearchSubstitutionsOnlyBytes {
+    pub fn find(pattern: &str, text: &str, max_distance: usize) -> Self {
+        Self::search(
+            &Pattern::literal(pattern),
+            text,
+            &FuzzySearchOptions::new(max_distance),
+        )
+    }
+
+    /// Search using a parsed pattern. Wildcards are supported, but gaps are not since they change the match length
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_pattern(
+        pattern: &Pattern,
+        text: &str,
+        max_distance: usize,
+    ) -> Result<Self, PatternError> {
+        Self::find_with_options(pattern, text, &FuzzySearchOptions::new(max_distance))
+    }
+
+    /// Search using options, the distance is limited by both max_total_distance and max_substitutions
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_with_options(
+        pattern: &Pattern,
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Result<Self, PatternError> {
+        if pattern.has_gaps() {
+            return Err(PatternError::GapsNotSupported);
+        }
+
+        Ok(Self::search(pattern, text, options))
+    }
+
+    fn search(pattern: &Pattern, text: &str, options: &FuzzySearchOptions) -> Self {
+        let max_distance = options.max_substitutions_only_distance();
+        let prepared = pattern.prepare(options);
+
+        let byte_pattern = match options.char_matcher.is_none() && max_distance < u8::MAX as usize {
+            true => Self::byte_elements(prepared.elements()).and_then(|elements| {
+                let bytes = elements.iter().filter_map(|e| match e {
+                    ByteElement::Char { byte, .. } => Some(*byte),
+                    ByteElement::Nucleotides { .. } => None,
+                });
+                other_byte(bytes).map(|b| (elements, b))
+            }),
+            false => None,
+        };
+
+        let Some((elements, other_byte)) = byte_pattern else {
+            return Self::with_fallback(FuzzySearchSubstitutionsOnly::search(
+                pattern, text, options,
+            ));
+        };
+
+        let text_chars = text.chars().collect::<Vec<_>>();
+
+        let (text_chars, original) = match NormalizedText::normalize(&text_chars, options) {
+            Some(normalized) => (normalized.chars, Some((text_chars, normalized.offsets))),
+            None => (text_chars, None),
+        };
+
+        let text_masks = match options.alphabet {
+            Alphabet::Dna { text_ambiguity } => text_chars
+                .iter()
+                .map(|c| nucleotide::text_mask(*c, text_ambiguity))
+                .collect(),
+            Alphabet::Text => Vec::new(),
+        };
+
+        let text_bytes = text_chars
+            .iter()
+            .map(|c| u8::try_from(*c).unwrap_or(other_byte))
+            .collect();
+
+        let allowed_starts = options.allowed_match_starts(&text_chars, prepared.len());
+        let last_index = match text_chars.len() >= prepared.len() {
+            true => text_chars.len() - prepared.len() + 1,
+            false => 0, // pattern longer than the text can never match
+        };
+
+        Self {
+            elements,
+            pattern_length: prepared.len(),
+            text_bytes,
+            text_masks,
+            current_text_index: if prepared.is_empty() || text_chars.is_empty() {
+                last_index
+            } else {
+                0
+            },
+            text_chars,
+            original,
+            max_distance,
+            allowed_starts,
+            last_index,
+            simd_level: SimdLevel::detect(),
+            block_index: 0,
+            block_hits: 0,
+            block_distances: [0; 64],
+            fallback: None,
+        }
+    }
+
+    /// Forces the kernel used for the search instead of the best one detected for the cpu, mostly useful for testing and benchmarks
+    ///
+    /// # Panics
+    /// If the cpu does not support the level
+    pub fn with_simd_level(mut self, simd_level: SimdLevel) -> Self {
+        assert!(
+            simd_level.is_supported(),
+            "{simd_level:?} is not supported by the cpu"
+        );
+        self.simd_level = simd_level;
+        self
+    }
+
+    fn with_fallback(fallback: FuzzySearchSubstitutionsOnly) -> Self {
+        Self {
+            elements: Vec::new(),
+            pattern_length: 0,
+            text_bytes: Vec::new(),
+            text_masks: Vec::new(),
+            text_chars: Vec::new(),
+            original: None,
+            max_distance: 0,
+            allowed_starts: None,
+            current_text_index: 0,
+            last_index: 0,
+            simd_level: SimdLevel::Scalar,
+            block_index: 0,
+            block_hits: 0,
+            block_distances: [0; 64],
+            fallback: Some(fallback),
+        }
+    }
+
+    /// The compared pattern elements, or None if some element cannot be compared as a byte
+    fn byte_elements(pattern: &[PatternElement]) -> Option<Vec<ByteElement>> {
+        pattern
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !matches!(e, PatternElement::Any))
+            .map(|(offset, e)| match e {
+                PatternElement::Char(c) => u8::try_from(*c)
+                    .ok()
+                    .map(|byte| ByteElement::Char { offset, byte }),
+                PatternElement::Nucleotides { mask, .. } => Some(ByteElement::Nucleotides {
+                    offset,
+                    mask: *mask,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn create_match(&self, start_index: usize, distance: usize) -> MatchResult {
+        let end_index = start_index + self.pattern_length;
+
+        let (start_index, end_index, text_chars) = match &self.original {
+            Some((original_chars, offsets)) => {
+                let (start, end) = offsets.original_range(start_index, end_index);
+                (start, end, original_chars)
+            }
+            None => (start_index, end_index, &self.text_chars),
+        };
+
+        MatchResult {
+            start_index,
+            end_index,
+            distance,
+            weighted_distance: distance as f32,
+            match_text: text_chars[start_index..end_index]
+                .iter()
+                .collect::<String>(),
+            deletions: 0,
+            insertions: 0,
+            ignored: 0,
+            substitutions: distance,
+        }
+    }
+
+    /// Mismatch counts at the N start indexes from index, saturating at 255. The block must fit in the text
+    #[inline(always)]
+    fn distances<const N: usize>(&self, index: usize) -> Simd<u8, N> {
+        let one = Simd::splat(1);
+        let mut distance_vector: Simd<u8, N> = Simd::splat(0);
+
+        // a plain loop rather than a closure, so the body is inlined into the target feature kernels
+        for element in &self.elements {
+            let mismatch = match *element {
+                ByteElement::Char { offset, byte } => {
+                    let text_vector: Simd<u8, N> =
+                        Simd::from_slice(&self.text_bytes[index + offset..]);
+                    text_vector.simd_ne(Simd::splat(byte))
+                }
+                ByteElement::Nucleotides { offset, mask } => {
+                    let text_vector: Simd<u8, N> =
+                        Simd::from_slice(&self.text_masks[index + offset..]);
+                    (text_vector & Simd::splat(mask)).simd_eq(Simd::splat(0))
+                }
+            };
+
+            distance_vector = distance_vector.saturating_add(mismatch.to_simd().cast::<u8>() & one);
+        }
+
+        distance_vector
+    }
+
+    /// Distances and the bitmask of lanes within the distance for a block of N start indexes
+    #[inline(always)]
+    fn block<const N: usize>(&self, index: usize) -> ([u8; 64], u64) {
+        let distances = self.distances::<N>(index);
+        let hits = distances
+            .simd_le(Simd::splat(self.max_distance as u8))
+            .to_bitmask();
+
+        let mut block_distances = [0; 64];
+        block_distances[..N].copy_from_slice(distances.as_array());
+        (block_distances, hits)
+    }
+
+    // the same kernel compiled for each instruction set, with the lane count filling one register
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse4.2")]
+    unsafe fn block_sse42(&self, index: usize) -> ([u8; 64], u64) {
+        self.block::<16>(index)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn block_avx2(&self, index: usize) -> ([u8; 64], u64) {
+        self.block::<32>(index)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn block_avx512(&self, index: usize) -> ([u8; 64], u64) {
+        self.block::<64>(index)
+    }
+
+    /// Searches whole blocks of N start indexes until one has hits, returns false when no full block is left
+    #[inline(always)]
+    fn next_block<const N: usize>(
+        &mut self,
+        kernel: impl Fn(&Self, usize) -> ([u8; 64], u64),
+    ) -> bool {
+        while self.current_text_index + N <= self.last_index {
+            let index = self.current_text_index;
+            self.current_text_index += N;
+
+            let (distances, hits) = kernel(self, index);
+
+            if hits != 0 {
+                self.block_index = index;
+                self.block_distances = distances;
+                self.block_hits = hits;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Distance at a single start index, or None if it exceeds the max distance
+    fn distance_at(&self, index: usize) -> Option<usize> {
+        self.elements.iter().try_fold(0, |distance, element| {
+            let matches = match *element {
+                ByteElement::Char { offset, byte } => self.text_bytes[index + offset] == byte,
+                ByteElement::Nucleotides { offset, mask } => {
+                    self.text_masks[index + offset] & mask != 0
+                }
+            };
+
+            match (matches, distance < self.max_distance) {
+                (true, _) => Some(distance),
+                (false, true) => Some(distance + 1),
+                (false, false) => None,
+            }
+        })
+    }
+}
+
+/// A byte none of the pattern bytes are equal to, used for text chars outside latin 1
+pub(crate) fn other_byte(pattern_bytes: impl Iterator<Item = u8>) -> Option<u8> {
+    let mut used = [false; 256];
+    for byte in pattern_bytes {
+        used[byte as usize] = true;
+    }
+
+    (0..=u8::MAX).rev().find(|b| !used[*b as usize])
+}
+
+impl Iterator for FuzzySearchSubstitutionsOnlyBytes {
+    type Item = MatchResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fallback) = &mut self.fallback {
+            return fallback.next();
+        }
+
+        loop {
+            while self.block_hits != 0 {
+                let lane = self.block_hits.trailing_zeros() as usize;
+                self.block_hits &= self.block_hits - 1;
+
+                let index = self.block_index + lane;
+                if is_allowed_start(&self.allowed_starts, index) {
+                    return Some(self.create_match(index, self.block_distances[lane] as usize));
+                }
+            }
+
+            let found = match self.simd_level {
+                SimdLevel::Scalar => false,
+                SimdLevel::Portable => self.next_block::<64>(Self::block::<64>),
+                #[cfg(target_arch = "x86_64")]
+                SimdLevel::Sse42 => self.next_block::<16>(|s, i| unsafe { s.block_sse42(i) }),
+                #[cfg(target_arch = "x86_64")]
+                SimdLevel::Avx2 => self.next_block::<32>(|s, i| unsafe { s.block_avx2(i) }),
+                #[cfg(target_arch = "x86_64")]
+                SimdLevel::Avx512 => self.next_block::<64>(|s, i| unsafe { s.block_avx512(i) }),
+                #[cfg(not(target_arch = "x86_64"))]
+                _ => self.next_block::<64>(Self::block::<64>),
+            };
+
+            if !found {
+                break;
+            }
+        }
+
+        // the remaining start indexes which do not fill a block
+        while self.current_text_index < self.last_index {
+            let index = self.current_text_index;
+            self.current_text_index += 1;
+
+            if !is_allowed_start(&self.allowed_starts, index) {
+                continue;
+            }
+
+            if let Some(distance) = self.distance_at(index) {
+                return Some(self.create_match(index, distance));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_substitutions_only_bytes_tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        char_matcher::EquivalenceMatcher,
+        fuzzy_search_options::{CaseFolding, SearchMode, WordBoundary},
+    };
+
+    fn summary(matches: impl Iterator<Item = MatchResult>) -> Vec<(usize, usize, usize, String)> {
+        matches
+            .map(|m| (m.start_index, m.end_index, m.distance, m.match_text))
+            .collect()
+    }
+
+    /// Asserts identical results to the scalar search with every simd level the cpu supports, returns the match count
+    fn assert_same(pattern: &Pattern, text: &str, options: &FuzzySearchOptions) -> usize {
+        let expected = summary(
+            FuzzySearchSubstitutionsOnly::find_with_options(pattern, text, options).unwrap(),
+        );
+
+        let levels = [
+            SimdLevel::Scalar,
+            SimdLevel::Portable,
+            SimdLevel::Sse42,
+            SimdLevel::Avx2,
+            SimdLevel::Avx512,
+        ];
+
+        for level in levels.into_iter().filter(|l| l.is_supported()) {
+            let actual = summary(
+                FuzzySearchSubstitutionsOnlyBytes::find_with_options(pattern, text, options)
+                    .unwrap()
+                    .with_simd_level(level),
+            );
+
+            assert_eq!(expected, actual, "{level:?}");
+        }
+
+        expected.len()
+    }
+
+    #[test]
+    fn test_text() {
+        let text = "--------patxexn----------pattern---------------paxxern---pattern".repeat(5);
+        let options = FuzzySearchOptions::new(2);
+
+        assert_eq!(
+            20,
+            assert_same(&Pattern::literal("pattern"), &text, &options)
+        );
+    }
+
+    #[test]
+    fn test_chars_outside_latin1() {
+        let text = "👩‍👩‍👦‍👦PATTERN-日本語-PATTÉRN-".repeat(10);
+        let options = FuzzySearchOptions::new(1);
+
+        assert_eq!(
+            20,
+            assert_same(&Pattern::literal("PATTERN"), &text, &options)
+        );
+        assert_eq!(
+            10,
+            assert_same(&Pattern::literal("日本語"), &text, &options)
+        );
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let text = "--INV-0042-2023--INV-0043-2O23--".repeat(5);
+        let options = FuzzySearchOptions::new(1);
+
+        assert_eq!(
+            10,
+            assert_same(&Pattern::parse("INV-????-2023").unwrap(), &text, &options)
+        );
+    }
+
+    #[test]
+    fn test_classes_and_char_matcher() {
+        let text = "12A-O4B--x9Z--a1b--15ö--".repeat(5);
+        let mut options = FuzzySearchOptions::new(1);
+
+        assert_same(
+            &Pattern::parse(r"[0-9OIl]\d\p{Lu}").unwrap(),
+            &text,
+            &options,
+        );
+
+        options.char_matcher = Some(Arc::new(EquivalenceMatcher::look_alikes()));
+        assert_same(&Pattern::literal("O4B"), &text, &options);
+    }
+
+    #[test]
+    fn test_dna() {
+        let text = "acgtacgtnnacgatcgatcgatgcatgcatcgatcgtagctagcatcgaTTRGCAtagctagctagcatcgactacgatcgatgactagcatttagc".repeat(3);
+
+        for text_ambiguity in [false, true] {
+            let mut options = FuzzySearchOptions::new(1);
+            options.alphabet = Alphabet::Dna { text_ambiguity };
+
+            assert!(assert_same(&Pattern::literal("TTAGCA"), &text, &options) > 0);
+            assert!(assert_same(&Pattern::literal("NNRGCA"), &text, &options) > 0);
+        }
+    }
+
+    #[test]
+    fn test_normalization_and_boundaries() {
+        let text =
+            "Die STRASSE und die Straße, Malmö--Malmo\u{308}--Malme art department ".repeat(3);
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+        options.ignore_diacritics = true;
+
+        assert_eq!(6, assert_same(&Pattern::literal("straße"), &text, &options));
+        assert_eq!(9, assert_same(&Pattern::literal("malmo"), &text, &options));
+
+        options.word_boundary = WordBoundary::Both;
+        assert_eq!(3, assert_same(&Pattern::literal("art"), &text, &options));
+
+        for search_mode in [SearchMode::Prefix, SearchMode::Suffix, SearchMode::Whole] {
+            options.search_mode = search_mode;
+            assert_same(&Pattern::literal("die"), &text, &options);
+        }
+    }
+
+    #[test]
+    fn test_long_pattern() {
+        // mismatch counts saturate in byte lanes, which must not turn distant positions into matches
+        let pattern = "abcdefghij".repeat(30);
+        let text = "-".repeat(100) + &pattern + &"abcdefghij".repeat(40);
+
+        assert_eq!(
+            41,
+            assert_same(
+                &Pattern::literal(&pattern),
+                &text,
+                &FuzzySearchOptions::new(3)
+            )
+        );
+        assert_same(
+            &Pattern::literal(&pattern),
+            &text,
+            &FuzzySearchOptions::new(300),
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let options = FuzzySearchOptions::new(1);
+
+        assert_eq!(0, assert_same(&Pattern::literal(""), "foo", &options));
+        assert_eq!(0, assert_same(&Pattern::literal("foo"), "", &options));
+        assert_eq!(0, assert_same(&Pattern::literal("foo"), "fo", &options));
+        assert_eq!(0, assert_same(&Pattern::literal(""), "", &options));
+    }
+
+    #[test]
+    fn test_gap_pattern() {
+        let pattern = Pattern::parse("Dear *{0,10},").unwrap();
+        let search = FuzzySearchSubstitutionsOnlyBytes::find_pattern(&pattern, "Dear John,", 1);
+
+        assert!(matches!(search, Err(PatternError::GapsNotSupported)));
+    }
+}
@@ -0,0 +1,603 @@
+use std::{
+    simd::{
+        prelude::{SimdInt, SimdPartialEq, SimdPartialOrd, SimdUint},
+        Mask, Simd,
+    },
+    sync::Arc,
+};
+
+use crate::{
+    char_matcher::CharMatcher,
+    fuzzy_search_options::{Alphabet, FuzzySearchOptions},
+    fuzzy_search_substitutions_only::is_allowed_start,
+    fuzzy_search_substitutions_only_bytes::other_byte,
+    match_result::ClosestPatternMatch,
+    normalized_text::{NormalizedText, OffsetMap},
+    nucleotide,
+    pattern::{Pattern, PatternElement, PatternError},
+};
+
+const LANES: usize = 64;
+
+// distances are counted in bytes, longer patterns could saturate them
+const MAX_PATTERN_LENGTH: usize = u8::MAX as usize - 1;
+
+/// Pattern elements at the same offset in up to 64 patterns, one pattern per lane
+struct Column {
+    chars: Simd<u8, LANES>,
+    char_lanes: Mask<i8, LANES>,
+    masks: Simd<u8, LANES>, // nucleotide masks, 0 in lanes without nucleotides
+    any_lanes: Mask<i8, LANES>,
+}
+
+/// Columns of up to 64 patterns
+struct Chunk {
+    columns: Vec<Column>,
+    initial: Simd<u8, LANES>, // 0 for the patterns, and the max for unused lanes so they are never the closest
+}
+
+/// Substitutions only search for the closest of many equal length patterns, eg barcodes when demultiplexing reads.
+/// Each text char of the window is broadcast once and compared against 64 patterns at a time, one pattern per byte lane.
+/// Patterns which cannot be compared as bytes, eg with chars outside latin 1, are compared char by char instead
+pub struct FuzzySearchSubstitutionsOnlyMulti {
+    chunks: Vec<Chunk>,
+    scalar_patterns: Option<Vec<Vec<PatternElement>>>, // set if the patterns are compared char by char, the chunks are empty then
+    char_matcher: Option<Arc<dyn CharMatcher>>,
+    pattern_length: usize,
+    has_nucleotides: bool,
+    text_bytes: Vec<u8>,
+    text_masks: Vec<u8>, // nucleotide bitmasks of the text chars, only used with the dna alphabet
+    text_chars: Vec<char>,
+    original: Option<(Vec<char>, OffsetMap)>, // set if the text was normalized, used for mapping matches back to the original text
+    max_distance: usize,
+    allowed_starts: Option<Vec<bool>>, // set if matches must respect word boundaries
+    current_text_index: usize,
+    last_index: usize,
+}
+
+impl FuzzySearchSubstitutionsOnlyMulti {
+    /// # Panics
+    /// If the patterns differ in length
+    pub fn find(patterns: &[&str], text: &str, max_distance: usize) -> Self {
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::literal(p))
+            .collect::<Vec<_>>();
+
+        Self::search(&patterns, text, &FuzzySearchOptions::new(max_distance))
+    }
+
+    /// Search using parsed patterns and options, the distance is limited by both max_total_distance and max_substitutions.
+    /// Wildcards, classes and nucleotide codes are supported
+    ///
+    /// # Errors
+    /// If a pattern contains gaps
+    ///
+    /// # Panics
+    /// If the patterns differ in length
+    pub fn find_with_options(
+        patterns: &[Pattern],
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Result<Self, PatternError> {
+        if patterns.iter().any(|p| p.has_gaps()) {
+            return Err(PatternError::GapsNotSupported);
+        }
+
+        Ok(Self::search(patterns, text, options))
+    }
+
+    fn search(patterns: &[Pattern], text: &str, options: &FuzzySearchOptions) -> Self {
+        let prepared = patterns
+            .iter()
+            .map(|p| p.prepare(options))
+            .collect::<Vec<_>>();
+        let pattern_length = prepared.first().map_or(0, |p| p.len());
+
+        for pattern in &prepared {
+            assert!(
+                pattern.len() == pattern_length,
+                "all patterns must have the same length"
+            );
+        }
+
+        // text chars outside latin 1 never match a pattern char, so they are mapped to a byte which is not in the patterns
+        let other_byte = other_byte(prepared.iter().flat_map(|p| {
+            p.elements().iter().filter_map(|e| match e {
+                PatternElement::Char(c) => u8::try_from(*c).ok(),
+                _ => None,
+            })
+        }));
+        let bytes = options.char_matcher.is_none()
+            && pattern_length <= MAX_PATTERN_LENGTH
+            && other_byte.is_some()
+            && prepared.iter().flat_map(|p| p.elements()).all(|e| match e {
+                PatternElement::Char(c) => u8::try_from(*c).is_ok(),
+                PatternElement::Any | PatternElement::Nucleotides { .. } => true,
+                PatternElement::Class(_) | PatternElement::Gap { .. } => false,
+            });
+
+        let (chunks, scalar_patterns) = match bytes {
+            true => (
+                prepared
+                    .chunks(LANES)
+                    .map(|chunk| Self::chunk(chunk, pattern_length))
+                    .collect::<Vec<_>>(),
+                None,
+            ),
+            false => (
+                Vec::new(),
+                Some(prepared.iter().map(|p| p.elements().to_vec()).collect()),
+            ),
+        };
+
+        let text_chars = text.chars().collect::<Vec<_>>();
+
+        let (text_chars, original) = match NormalizedText::normalize(&text_chars, options) {
+            Some(normalized) => (normalized.chars, Some((text_chars, normalized.offsets))),
+            None => (text_chars, None),
+        };
+
+        let text_masks = match options.alphabet {
+            Alphabet::Dna { text_ambiguity } => text_chars
+                .iter()
+                .map(|c| nucleotide::text_mask(*c, text_ambiguity))
+                .collect(),
+            Alphabet::Text => Vec::new(),
+        };
+
+        let text_bytes = match other_byte {
+            Some(other_byte) if bytes => text_chars
+                .iter()
+                .map(|c| u8::try_from(*c).unwrap_or(other_byte))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let allowed_starts = options.allowed_match_starts(&text_chars, pattern_length);
+        let last_index = match text_chars.len() >= pattern_length {
+            true => text_chars.len() - pattern_length + 1,
+            false => 0, // patterns longer than the text can never match
+        };
+
+        Self {
+            has_nucleotides: prepared
+                .iter()
+                .flat_map(|p| p.elements())
+                .any(|e| matches!(e, PatternElement::Nucleotides { .. })),
+            chunks,
+            scalar_patterns,
+            char_matcher: options.char_matcher.clone(),
+            pattern_length,
+            text_bytes,
+            text_masks,
+            current_text_index: if pattern_length == 0 { last_index } else { 0 },
+            text_chars,
+            original,
+            // no distance can exceed the pattern length, which keeps the limit within the u8 lanes
+            max_distance: options
+                .max_substitutions_only_distance()
+                .min(pattern_length),
+            allowed_starts,
+            last_index,
+        }
+    }
+
+    /// Transposes up to 64 patterns into columns
+    fn chunk(patterns: &[Pattern], pattern_length: usize) -> Chunk {
+        let columns = (0..pattern_length)
+            .map(|offset| {
+                let element = |lane: usize| patterns.get(lane).map(|p| &p.elements()[offset]);
+                let lanes = |f: fn(&PatternElement) -> bool| {
+                    Mask::from_array(std::array::from_fn(|lane| element(lane).is_some_and(f)))
+                };
+
+                Column {
+                    chars: Simd::from_array(std::array::from_fn(|lane| match element(lane) {
+                        Some(PatternElement::Char(c)) => u8::try_from(*c).unwrap_or(0),
+                        _ => 0,
+                    })),
+                    char_lanes: lanes(|e| matches!(e, PatternElement::Char(_))),
+                    masks: Simd::from_array(std::array::from_fn(|lane| match element(lane) {
+                        Some(PatternElement::Nucleotides { mask, .. }) => *mask,
+                        _ => 0,
+                    })),
+                    any_lanes: lanes(|e| matches!(e, PatternElement::Any)),
+                }
+            })
+            .collect();
+
+        Chunk {
+            columns,
+            initial: Simd::from_array(std::array::from_fn(|lane| match lane < patterns.len() {
+                true => 0,
+                false => u8::MAX,
+            })),
+        }
+    }
+
+    /// The lowest distance within the limit at the index and the patterns with it
+    #[inline(always)]
+    fn closest(&self, index: usize) -> Option<(usize, Vec<usize>)> {
+        if let Some(patterns) = &self.scalar_patterns {
+            return self.closest_scalar(patterns, index);
+        }
+
+        let one = Simd::splat(1);
+        let mut best = self.max_distance as u8;
+        let mut closest = Vec::new();
+
+        for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+            let mut distances = chunk.initial;
+
+            for (offset, column) in chunk.columns.iter().enumerate() {
+                let text_byte = Simd::splat(self.text_bytes[index + offset]);
+                let mut matches =
+                    (text_byte.simd_eq(column.chars) & column.char_lanes) | column.any_lanes;
+
+                if self.has_nucleotides {
+                    let text_mask = Simd::splat(self.text_masks[index + offset]);
+                    matches |= (text_mask & column.masks).simd_ne(Simd::splat(0));
+                }
+
+                distances = distances.saturating_add((!matches).to_simd().cast::<u8>() & one);
+
+                // stop early once no pattern of the chunk can get as close as the best so far
+                if offset % 8 == 7 && distances.simd_gt(Simd::splat(best)).all() {
+                    break;
+                }
+            }
+
+            let min = distances.reduce_min();
+
+            if min > best {
+                continue;
+            } else if min < best || closest.is_empty() {
+                best = min;
+                closest.clear();
+            }
+
+            let mut lanes = distances.simd_eq(Simd::splat(min)).to_bitmask();
+            while lanes != 0 {
+                closest.push(chunk_index * LANES + lanes.trailing_zeros() as usize);
+                lanes &= lanes - 1;
+            }
+        }
+
+        (!closest.is_empty()).then_some((best as usize, closest))
+    }
+
+    /// Same as closest, comparing the chars of one pattern at a time
+    fn closest_scalar(
+        &self,
+        patterns: &[Vec<PatternElement>],
+        index: usize,
+    ) -> Option<(usize, Vec<usize>)> {
+        let text = &self.text_chars[index..index + self.pattern_length];
+        let mut best = self.max_distance;
+        let mut closest = Vec::new();
+
+        for (id, pattern) in patterns.iter().enumerate() {
+            let mut distance = 0;
+
+            for (element, c) in pattern.iter().zip(text) {
+                if !element.matches_with(*c, self.char_matcher.as_deref()) {
+                    distance += 1;
+
+                    if distance > best {
+                        break;
+                    }
+                }
+            }
+
+            if distance < best {
+                best = distance;
+                closest.clear();
+            }
+            if distance == best {
+                closest.push(id);
+            }
+        }
+
+        (!closest.is_empty()).then_some((best, closest))
+    }
+
+    fn create_match(
+        &self,
+        start_index: usize,
+        distance: usize,
+        closest: Vec<usize>,
+    ) -> ClosestPatternMatch {
+        let end_index = start_index + self.pattern_length;
+
+        let (start_index, end_index, text_chars) = match &self.original {
+            Some((original_chars, offsets)) => {
+                let (start, end) = offsets.original_range(start_index, end_index);
+                (start, end, original_chars)
+            }
+            None => (start_index, end_index, &self.text_chars),
+        };
+
+        ClosestPatternMatch {
+            start_index,
+            end_index,
+            match_text: text_chars[start_index..end_index]
+                .iter()
+                .collect::<String>(),
+            pattern_id: closest[0],
+            distance,
+            tied_pattern_ids: closest[1..].to_vec(),
+        }
+    }
+}
+
+impl Iterator for FuzzySearchSubstitutionsOnlyMulti {
+    type Item = ClosestPatternMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current_text_index < self.last_index {
+            let index = self.current_text_index;
+            self.current_text_index += 1;
+
+            if !is_allowed_start(&self.allowed_starts, index) {
+                continue;
+            }
+
+            if let Some((distance, closest)) = self.closest(index) {
+                return Some(self.create_match(index, distance, closest));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod fuzzy_search_substitutions_only_multi_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        fuzzy_search_options::CaseFolding,
+        fuzzy_search_substitutions_only::FuzzySearchSubstitutionsOnly,
+    };
+
+    /// Closest patterns at each start index, found with a separate search for each pattern
+    fn expected(
+        patterns: &[Pattern],
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Vec<(usize, usize, Vec<usize>)> {
+        let mut closest: BTreeMap<usize, (usize, Vec<usize>)> = BTreeMap::new();
+
+        for (id, pattern) in patterns.iter().enumerate() {
+            for m in
+                FuzzySearchSubstitutionsOnly::find_with_options(pattern, text, options).unwrap()
+            {
+                let entry = closest
+                    .entry(m.start_index)
+                    .or_insert((m.distance, Vec::new()));
+
+                if m.distance < entry.0 {
+                    *entry = (m.distance, Vec::new());
+                }
+                if m.distance == entry.0 {
+                    entry.1.push(id);
+                }
+            }
+        }
+
+        closest
+            .into_iter()
+            .map(|(start, (distance, ids))| (start, distance, ids))
+            .collect()
+    }
+
+    fn actual(
+        patterns: &[Pattern],
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Vec<(usize, usize, Vec<usize>)> {
+        FuzzySearchSubstitutionsOnlyMulti::find_with_options(patterns, text, options)
+            .unwrap()
+            .map(|m| {
+                let ids = std::iter::once(m.pattern_id)
+                    .chain(m.tied_pattern_ids)
+                    .collect();
+                (m.start_index, m.distance, ids)
+            })
+            .collect()
+    }
+
+    /// Deterministic pseudo random DNA
+    fn dna(length: usize, seed: u64) -> String {
+        let mut state = seed;
+        (0..length)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ['A', 'C', 'G', 'T'][(state >> 33) as usize % 4]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_barcodes() {
+        // more than 64 codes, so several chunks are compared at each position
+        let barcodes = (0..100).map(|i| dna(8, i)).collect::<Vec<_>>();
+        let text = dna(2000, 1000) + &barcodes[70] + &dna(100, 1001) + &barcodes[3];
+        let patterns = barcodes
+            .iter()
+            .map(|b| Pattern::literal(b))
+            .collect::<Vec<_>>();
+
+        for max_distance in [0, 1, 2] {
+            let options = FuzzySearchOptions::new(max_distance);
+            let expected = expected(&patterns, &text, &options);
+
+            assert_eq!(expected, actual(&patterns, &text, &options));
+            assert!(expected
+                .iter()
+                .any(|(start, d, ids)| *start == 2000 && *d == 0 && ids == &[70]));
+        }
+
+        let ties = actual(&patterns, &text, &FuzzySearchOptions::new(2));
+        assert!(ties.iter().any(|(_, _, ids)| ids.len() > 1));
+    }
+
+    #[test]
+    fn test_ties() {
+        let text = "--ACGT--ACGA--";
+        let matches = FuzzySearchSubstitutionsOnlyMulti::find(&["ACGA", "ACGC", "ACGT"], text, 1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(2, matches.len());
+        assert_eq!(
+            (2, 2, 0),
+            (
+                matches[0].start_index,
+                matches[0].pattern_id,
+                matches[0].distance
+            )
+        );
+        assert!(matches[0].tied_pattern_ids.is_empty());
+        assert_eq!("ACGT", matches[0].match_text);
+        assert_eq!(
+            (8, 0, 0),
+            (
+                matches[1].start_index,
+                matches[1].pattern_id,
+                matches[1].distance
+            )
+        );
+
+        let matches = FuzzySearchSubstitutionsOnlyMulti::find(&["ACGA", "ACGC", "ACGT"], "ACGG", 1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(0, matches[0].pattern_id);
+        assert_eq!(vec![1, 2], matches[0].tied_pattern_ids);
+    }
+
+    #[test]
+    fn test_wildcards_and_nucleotides() {
+        let text = dna(500, 7) + "ACGTNNACGT" + &dna(500, 8);
+        let patterns = [
+            Pattern::literal("ACGTRYACGT"),
+            Pattern::parse("ACG??TACGT").unwrap(),
+            Pattern::literal("TTTTTTTTTT"),
+        ];
+
+        for text_ambiguity in [false, true] {
+            let mut options = FuzzySearchOptions::new(2);
+            options.alphabet = Alphabet::Dna { text_ambiguity };
+
+            assert_eq!(
+                expected(&patterns, &text, &options),
+                actual(&patterns, &text, &options)
+            );
+        }
+    }
+
+    #[test]
+    fn test_text_options() {
+        let text = "Invoice INV-0042 and inv-0043, ref INV-日本-2023 and ÄNV-0042";
+        let patterns = [Pattern::literal("inv-0042"), Pattern::literal("inv-0043")];
+        let mut options = FuzzySearchOptions::new(1);
+
+        assert_eq!(
+            expected(&patterns, text, &options),
+            actual(&patterns, text, &options)
+        );
+
+        options.case_folding = CaseFolding::Simple;
+        options.ignore_diacritics = true;
+        options.word_boundary = crate::fuzzy_search_options::WordBoundary::Both;
+
+        let actual = actual(&patterns, text, &options);
+        assert_eq!(expected(&patterns, text, &options), actual);
+        assert_eq!(3, actual.len());
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(
+            0,
+            FuzzySearchSubstitutionsOnlyMulti::find(&[], "ACGT", 1).count()
+        );
+        assert_eq!(
+            0,
+            FuzzySearchSubstitutionsOnlyMulti::find(&["", ""], "ACGT", 1).count()
+        );
+        assert_eq!(
+            0,
+            FuzzySearchSubstitutionsOnlyMulti::find(&["ACGT"], "", 1).count()
+        );
+    }
+
+    #[test]
+    fn test_max_distance_above_u8() {
+        let closest = |max_distance| {
+            FuzzySearchSubstitutionsOnlyMulti::find(&["abc", "abd"], "xyzab", max_distance)
+                .map(|m| (m.start_index, m.distance))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![(0, 3), (1, 3), (2, 3)], closest(255));
+        assert_eq!(closest(255), closest(256));
+        assert_eq!(closest(255), closest(258));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_different_lengths() {
+        FuzzySearchSubstitutionsOnlyMulti::find(&["ACGT", "ACG"], "ACGT", 1);
+    }
+
+    #[test]
+    fn test_gap_pattern() {
+        let patterns = [Pattern::parse("A*{0,2}C").unwrap()];
+        let search = FuzzySearchSubstitutionsOnlyMulti::find_with_options(
+            &patterns,
+            "AC",
+            &FuzzySearchOptions::new(0),
+        );
+
+        assert!(matches!(search, Err(PatternError::GapsNotSupported)));
+    }
+
+    #[test]
+    fn test_patterns_outside_latin_1() {
+        let text = "東京都 and 京都府, 東京府 or 大阪府";
+        let patterns = [Pattern::literal("東京都"), Pattern::literal("京都府")];
+        let options = FuzzySearchOptions::new(1);
+
+        let actual = actual(&patterns, text, &options);
+        assert_eq!(expected(&patterns, text, &options), actual);
+        assert!(actual.contains(&(0, 0, vec![0])));
+        assert!(actual.contains(&(8, 0, vec![1])));
+    }
+
+    #[test]
+    fn test_classes_and_char_matcher() {
+        let text = "ref 12-34, ref 1Z-3S and ref ab-cd";
+        let patterns = [
+            Pattern::parse(r"\d\d-\d\d").unwrap(),
+            Pattern::literal("12-35"),
+        ];
+        let mut options = FuzzySearchOptions::new(1);
+
+        assert_eq!(
+            expected(&patterns, text, &options),
+            actual(&patterns, text, &options)
+        );
+
+        options.char_matcher = Some(Arc::new(
+            crate::char_matcher::EquivalenceMatcher::look_alikes(),
+        ));
+
+        let actual = actual(&patterns, text, &options);
+        assert_eq!(expected(&patterns, text, &options), actual);
+        assert!(actual.contains(&(15, 0, vec![1])));
+    }
+}
@@ -1,47 +1,304 @@
 use std::{
     collections::VecDeque,
     mem,
-    simd::{prelude::SimdPartialEq, Simd},
+    simd::{
+        prelude::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+    sync::Arc,
 };
 
-use crate::match_result::MatchResult;
+use crate::{
+    char_class::CharClass,
+    char_matcher::CharMatcher,
+    fuzzy_search_options::{Alphabet, FuzzySearchOptions},
+    fuzzy_search_substitutions_only::is_allowed_start,
+    match_result::MatchResult,
+    normalized_text::{NormalizedText, OffsetMap},
+    nucleotide,
+    pattern::{Pattern, PatternElement, PatternError},
+    simd_level::SimdLevel,
+};
 
 pub struct FuzzySearchSubstitutionsOnlySimdTest {
-    pattern_u32s: Vec<u32>,
+    pattern: Vec<PatternElement>,
+    compared_count: usize, // wildcards always match, so only chars and classes can add distance
     text_u32s: Vec<u32>,
+    text_masks: Vec<u32>, // nucleotide bitmasks of the text chars, only used with the dna alphabet
+    original: Option<(Vec<char>, OffsetMap)>, // set if the text was normalized, used for mapping matches back to the original text
     max_distance: usize,
+    allowed_starts: Option<Vec<bool>>, // set if matches must respect word boundaries
+    char_matcher: Option<Arc<dyn CharMatcher>>,
     current_text_index: usize,
     last_index: usize,
-    last_index_simd: usize,
+    simd_level: SimdLevel,
     match_buffer: VecDeque<MatchResult>, // since we are using simd, calling next may produce multiple matches.. they are buffered here and returned one by one
 }
 
-const LANES: usize = 64;
-
 impl FuzzySearchSubstitutionsOnlySimdTest {
     pub fn find(pattern: &str, text: &str, max_distance: usize) -> Self {
+        Self::search(
+            &Pattern::literal(pattern),
+            text,
+            &FuzzySearchOptions::new(max_distance),
+        )
+    }
+
+    /// Search using a parsed pattern. Wildcards are supported, but gaps are not since they change the match length
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_pattern(
+        pattern: &Pattern,
+        text: &str,
+        max_distance: usize,
+    ) -> Result<Self, PatternError> {
+        Self::find_with_options(pattern, text, &FuzzySearchOptions::new(max_distance))
+    }
+
+    /// Search using options, the distance is limited by both max_total_distance and max_substitutions
+    ///
+    /// # Errors
+    /// If the pattern contains gaps
+    pub fn find_with_options(
+        pattern: &Pattern,
+        text: &str,
+        options: &FuzzySearchOptions,
+    ) -> Result<Self, PatternError> {
+        if pattern.has_gaps() {
+            return Err(PatternError::GapsNotSupported);
+        }
+
+        Ok(Self::search(pattern, text, options))
+    }
+
+    fn search(pattern: &Pattern, text: &str, options: &FuzzySearchOptions) -> Self {
+        let pattern = pattern.prepare(options);
+        let text_chars = text.chars().collect::<Vec<_>>();
+
+        let (text_chars, original) = match NormalizedText::normalize(&text_chars, options) {
+            Some(normalized) => (normalized.chars, Some((text_chars, normalized.offsets))),
+            None => (text_chars, None),
+        };
+
+        let text_masks = match options.alphabet {
+            Alphabet::Dna { text_ambiguity } => text_chars
+                .iter()
+                .map(|c| nucleotide::text_mask(*c, text_ambiguity) as u32)
+                .collect(),
+            Alphabet::Text => Vec::new(),
+        };
+
+        let allowed_starts = options.allowed_match_starts(&text_chars, pattern.len());
+
         unsafe {
-            let text_u32s: Vec<u32> = mem::transmute(text.chars().collect::<Vec<_>>());
-            let pattern_u32s: Vec<u32> = mem::transmute(pattern.chars().collect::<Vec<_>>());
+            let text_u32s: Vec<u32> = mem::transmute(text_chars);
             let length = text_u32s.len();
-            let last_index = text_u32s.len().checked_sub(pattern_u32s.len()).unwrap_or(0) + 1;
+            let last_index = match length >= pattern.len() {
+                true => length - pattern.len() + 1,
+                false => 0, // pattern longer than the text can never match
+            };
 
             Self {
                 text_u32s,
-                pattern_u32s,
-                max_distance,
-                current_text_index: if pattern.len() == 0 || text.len() == 0 {
+                text_masks,
+                original,
+                pattern: pattern.elements().to_vec(),
+                compared_count: pattern
+                    .elements()
+                    .iter()
+                    .filter(|e| !matches!(e, PatternElement::Any))
+                    .count(),
+                max_distance: options.max_substitutions_only_distance(),
+                allowed_starts,
+                char_matcher: options.char_matcher.clone(),
+                current_text_index: if pattern.is_empty() || text.is_empty() {
                     // this is basically here to eagerly terminate stuff if pattern is an empty string without checking in the next function
                     length + 1
                 } else {
                     0
                 },
                 last_index,
-                last_index_simd: last_index.checked_sub(LANES).unwrap_or(0),
+                simd_level: SimdLevel::detect(),
                 match_buffer: VecDeque::new(),
             }
         }
     }
+
+    /// Forces the kernel used for the search instead of the best one detected for the cpu, mostly useful for testing and benchmarks
+    ///
+    /// # Panics
+    /// If the cpu does not support the level
+    pub fn with_simd_level(mut self, simd_level: SimdLevel) -> Self {
+        assert!(
+            simd_level.is_supported(),
+            "{simd_level:?} is not supported by the cpu"
+        );
+        self.simd_level = simd_level;
+        self
+    }
+}
+
+impl FuzzySearchSubstitutionsOnlySimdTest {
+    fn create_match(&self, start_index: usize, distance: usize) -> MatchResult {
+        let end_index = start_index + self.pattern.len();
+
+        let (start_index, end_index, text_chars) = match &self.original {
+            Some((original_chars, offsets)) => {
+                let (start, end) = offsets.original_range(start_index, end_index);
+                (start, end, &original_chars[..])
+            }
+            None => unsafe {
+                let text_chars: &[char] = mem::transmute(&self.text_u32s[..]);
+                (start_index, end_index, text_chars)
+            },
+        };
+
+        MatchResult {
+            start_index,
+            end_index,
+            distance,
+            weighted_distance: distance as f32,
+            match_text: text_chars[start_index..end_index]
+                .iter()
+                .collect::<String>(),
+            deletions: 0,
+            insertions: 0,
+            ignored: 0,
+            substitutions: distance,
+        }
+    }
+
+    /// Custom matchers cannot be vectorized, so they are checked lane by lane
+    #[inline(always)]
+    fn matcher_mask<const N: usize>(
+        matcher: &dyn CharMatcher,
+        pattern: char,
+        text_vector: Simd<u32, N>,
+    ) -> Mask<i32, N> {
+        let text = text_vector.to_array();
+        Mask::from_array(std::array::from_fn(|i| {
+            matcher.matches(pattern, unsafe { char::from_u32_unchecked(text[i]) })
+        }))
+    }
+
+    #[inline(always)]
+    fn class_mask<const N: usize>(class: &CharClass, text_vector: Simd<u32, N>) -> Mask<i32, N> {
+        if class.is_case_insensitive() {
+            let text = text_vector.to_array();
+            return Mask::from_array(std::array::from_fn(|i| {
+                class.contains(unsafe { char::from_u32_unchecked(text[i]) })
+            }));
+        }
+
+        let mut mask = class
+            .ranges()
+            .iter()
+            .fold(Mask::splat(false), |mask, (from, to)| {
+                mask | (text_vector.simd_ge(Simd::splat(*from as u32))
+                    & text_vector.simd_le(Simd::splat(*to as u32)))
+            });
+
+        // unicode categories cannot be vectorized, so they are checked lane by lane
+        if !class.categories().is_empty() {
+            let text = text_vector.to_array();
+            mask |= Mask::from_array(std::array::from_fn(|i| {
+                let c = unsafe { char::from_u32_unchecked(text[i]) };
+                class
+                    .categories()
+                    .iter()
+                    .any(|category| category.contains(c))
+            }));
+        }
+
+        match class.is_negated() {
+            true => !mask,
+            false => mask,
+        }
+    }
+
+    /// Distances of the pattern to the text at the N start indexes from index, the block must fit in the text
+    #[inline(always)]
+    fn distances<const N: usize>(&self, index: usize) -> [i32; N] {
+        assert!(self.text_u32s.len() >= index + N - 1 + self.pattern.len()); // so this actually does work :O perf gain around 5%, gets rid of the bounds check in the from_slice call
+
+        // using a mutable distance_vector here intead of fold yields slighly better performance in benchmarks.
+        // a plain loop rather than a closure, so the body is inlined into the target feature kernels
+        let mut distance_vector: Simd<i32, N> = Simd::splat(self.compared_count as i32);
+        for (i, p) in self.pattern.iter().enumerate() {
+            match p {
+                PatternElement::Char(c) => {
+                    let text_vector: Simd<u32, N> = Simd::from_slice(&self.text_u32s[index + i..]);
+
+                    distance_vector += match &self.char_matcher {
+                        Some(matcher) => Self::matcher_mask(matcher.as_ref(), *c, text_vector),
+                        None => SimdPartialEq::simd_eq(text_vector, Simd::splat(*c as u32)),
+                    }
+                    .to_simd();
+                }
+                PatternElement::Class(class) => {
+                    let text_vector: Simd<u32, N> = Simd::from_slice(&self.text_u32s[index + i..]);
+
+                    distance_vector += Self::class_mask(class, text_vector).to_simd();
+                }
+                PatternElement::Nucleotides { mask, .. } => {
+                    let mask_vector: Simd<u32, N> = Simd::splat(*mask as u32);
+                    let text_vector: Simd<u32, N> = Simd::from_slice(&self.text_masks[index + i..]);
+
+                    distance_vector += (text_vector & mask_vector)
+                        .simd_ne(Simd::splat(0))
+                        .to_simd();
+                }
+                _ => {}
+            }
+        }
+
+        distance_vector.to_array()
+    }
+
+    // the same kernel compiled for each instruction set, with the lane count filling 4 registers
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse4.2")]
+    unsafe fn distances_sse42(&self, index: usize) -> [i32; 16] {
+        self.distances::<16>(index)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn distances_avx2(&self, index: usize) -> [i32; 32] {
+        self.distances::<32>(index)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn distances_avx512(&self, index: usize) -> [i32; 64] {
+        self.distances::<64>(index)
+    }
+
+    /// Searches whole blocks of N start indexes with the kernel until some matches are buffered, returns false when no full block is left
+    #[inline(always)]
+    fn next_blocks<const N: usize>(&mut self, kernel: impl Fn(&Self, usize) -> [i32; N]) -> bool {
+        while self.current_text_index + N <= self.last_index {
+            let current_index = self.current_text_index;
+            self.current_text_index += N;
+
+            for (i, distance) in kernel(self, current_index).iter().enumerate() {
+                if *distance <= self.max_distance as i32
+                    && is_allowed_start(&self.allowed_starts, current_index + i)
+                {
+                    let m = self.create_match(current_index + i, *distance as usize);
+                    self.match_buffer.push_front(m);
+                }
+            }
+
+            if !self.match_buffer.is_empty() {
+                return true;
+            }
+        }
+
+        false
+    }
 }
 
 impl Iterator for FuzzySearchSubstitutionsOnlySimdTest {
@@ -52,100 +309,50 @@ impl Iterator for FuzzySearchSubstitutionsOnlySimdTest {
             return Some(m);
         }
 
-        let remaining_chars = self
-            .last_index
-            .checked_sub(self.current_text_index)
-            .unwrap_or(0);
+        let found = match self.simd_level {
+            SimdLevel::Scalar => false,
+            SimdLevel::Portable => self.next_blocks(Self::distances::<64>),
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Sse42 => self.next_blocks(|s, i| unsafe { s.distances_sse42(i) }),
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx2 => self.next_blocks(|s, i| unsafe { s.distances_avx2(i) }),
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx512 => self.next_blocks(|s, i| unsafe { s.distances_avx512(i) }),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => self.next_blocks(Self::distances::<64>),
+        };
+
+        if found {
+            return self.match_buffer.pop_back();
+        }
 
-        if remaining_chars >= LANES {
-            while self.current_text_index < self.last_index_simd {
-                let current_index = self.current_text_index;
-                self.current_text_index += LANES;
+        // this is here to handle the remaining chars which dont fit into lanes width
+        while self.current_text_index < self.last_index {
+            let current_index = self.current_text_index;
+            self.current_text_index += 1;
 
-                assert!(self.text_u32s.len() >= current_index + self.pattern_u32s.len()); // so this actually does work :O perf gain around 5%, gets rid of the bounds check in the from_slice call
+            if !is_allowed_start(&self.allowed_starts, current_index) {
+                continue;
+            }
 
-                // using a mutable distance_vector here intead of fold yields slighly better performance in benchmarks
-                let mut distance_vector: Simd<i32, LANES> =
-                    Simd::splat(self.pattern_u32s.len() as i32);
-                self.pattern_u32s.iter().enumerate().for_each(|(i, p)| {
-                    let pattern_vector: Simd<u32, LANES> = Simd::splat(*p);
-                    let text_vector: Simd<u32, LANES> =
-                        Simd::from_slice(&self.text_u32s[current_index + i..]);
+            let text_chars: &[char] = unsafe {
+                mem::transmute(&self.text_u32s[current_index..current_index + self.pattern.len()])
+            };
 
-                    distance_vector += SimdPartialEq::simd_eq(text_vector, pattern_vector).to_int();
-                });
+            let m = &text_chars.iter().zip(&self.pattern).try_fold(0, |a, v| {
+                let distance = match v.1.matches_with(*v.0, self.char_matcher.as_deref()) {
+                    true => a,
+                    false => a + 1,
+                };
 
-                distance_vector
-                    .as_array()
-                    .iter()
-                    .enumerate()
-                    .filter(|f| *f.1 <= self.max_distance as i32)
-                    .for_each(|(i, v)| {
-                        self.match_buffer.push_front(MatchResult {
-                            start_index: current_index + i,
-                            end_index: current_index + i + self.pattern_u32s.len(),
-                            distance: *v as usize,
-                            match_text: {
-                                unsafe {
-                                    let transmuted: &[char] = mem::transmute(
-                                        &self.text_u32s[current_index + i
-                                            ..(current_index + i + self.pattern_u32s.len())],
-                                    );
-
-                                    transmuted.iter().collect::<String>()
-                                }
-                            },
-                            deletions: 0,
-                            insertions: 0,
-                            substitutions: *v as usize,
-                        });
-                    });
-
-                if let Some(m) = self.match_buffer.pop_back() {
-                    return Some(m);
+                match distance > self.max_distance {
+                    true => None,
+                    false => Some(distance),
                 }
-            }
-        } else {
-            // this is here to handle the remaining chars which dont fit into lanes width
-            while self.current_text_index < self.last_index {
-                let current_index = self.current_text_index;
-                self.current_text_index += 1;
+            });
 
-                let m = &self.text_u32s[current_index..current_index + self.pattern_u32s.len()]
-                    .iter()
-                    .zip(&self.pattern_u32s)
-                    .try_fold(0, |a, v| {
-                        let distance = match v.0 == v.1 {
-                            true => a,
-                            false => a + 1,
-                        };
-
-                        match distance > self.max_distance {
-                            true => None,
-                            false => Some(distance),
-                        }
-                    });
-
-                if let Some(distance) = m {
-                    return Some(MatchResult {
-                        start_index: current_index,
-                        end_index: current_index + self.pattern_u32s.len(),
-                        distance: *distance,
-                        match_text: {
-                            unsafe {
-                                let transmuted: &[char] = mem::transmute(
-                                    &self.text_u32s
-                                        [current_index..(current_index + self.pattern_u32s.len())],
-                                );
-
-                                transmuted.iter().collect::<String>()
-                            }
-                        },
-                        deletions: 0,
-                        insertions: 0,
-                        substitutions: *distance,
-                    });
-                }
+            if let Some(distance) = m {
+                return Some(self.create_match(current_index, *distance));
             }
         }
 
@@ -156,6 +363,10 @@ impl Iterator for FuzzySearchSubstitutionsOnlySimdTest {
 #[cfg(test)]
 mod fuzzy_search_substitution_only_simd_tests {
     use super::*;
+    use crate::{
+        char_matcher::EquivalenceMatcher, fuzzy_search_options::SearchMode,
+        fuzzy_search_substitutions_only::FuzzySearchSubstitutionsOnly,
+    };
 
     #[test]
     fn test_something_simd() {
@@ -283,6 +494,318 @@ mod fuzzy_search_substitution_only_simd_tests {
         }
     }
 
+    #[test]
+    fn wildcard_pattern() {
+        let pattern = Pattern::parse("INV-????-2023").unwrap();
+        let text = "--INV-0042-2023--INV-0043-2O23-------------------------------------------------------------------------------------------------INV-9999-2023";
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_pattern(&pattern, text, 1)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(3, matches.len());
+        assert_match(2, 15, text, &matches[0]);
+        assert_eq!(0, matches[0].distance);
+        assert_match(17, 30, text, &matches[1]);
+        assert_eq!(1, matches[1].distance);
+        assert_match(127, 140, text, &matches[2]);
+        assert_eq!(0, matches[2].distance);
+    }
+
+    #[test]
+    fn class_pattern() {
+        let pattern = Pattern::parse(r"[0-9OIl]\d\p{Lu}").unwrap();
+        let text = "-----------------------------------------------------------------------------------------------------------------12A-O4B--x9Z--a1b--15ö--";
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_pattern(&pattern, text, 1)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let scalar_matches = FuzzySearchSubstitutionsOnly::find_pattern(&pattern, text, 1)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(4, matches.len());
+        assert_match(113, 116, text, &matches[0]);
+        assert_eq!(0, matches[0].distance);
+        assert_match(117, 120, text, &matches[1]);
+        assert_eq!(0, matches[1].distance);
+        assert_match(122, 125, text, &matches[2]);
+        assert_eq!(1, matches[2].distance);
+        assert_eq!(132, matches[3].start_index);
+        assert_eq!("15ö", matches[3].match_text);
+        assert_eq!(1, matches[3].distance);
+
+        for (m, scalar) in matches.iter().zip(&scalar_matches) {
+            assert_eq!(m.start_index, scalar.start_index);
+            assert_eq!(m.distance, scalar.distance);
+        }
+        assert_eq!(matches.len(), scalar_matches.len());
+    }
+
+    #[test]
+    fn dna_pattern() {
+        let text = "acgtacgtnnacgatcgatcgatgcatgcatcgatcgtagctagctagctagcatcgatcgatcgatcgagctagctagcatcgatcgaTTRGCAtagctagctagcatcgactacgatcgatgactagcatttagc";
+        let pattern = Pattern::literal("TTAGCA");
+
+        for text_ambiguity in [false, true] {
+            let mut options = FuzzySearchOptions::new(1);
+            options.alphabet = Alphabet::Dna { text_ambiguity };
+
+            let matches =
+                FuzzySearchSubstitutionsOnlySimdTest::find_with_options(&pattern, text, &options)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+            let scalar_matches =
+                FuzzySearchSubstitutionsOnly::find_with_options(&pattern, text, &options)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+
+            assert_eq!(
+                matches
+                    .iter()
+                    .map(|m| (m.start_index, m.distance))
+                    .collect::<Vec<_>>(),
+                scalar_matches
+                    .iter()
+                    .map(|m| (m.start_index, m.distance))
+                    .collect::<Vec<_>>()
+            );
+
+            let exact = matches.iter().find(|m| m.match_text == "TTRGCA").unwrap();
+            assert_eq!(if text_ambiguity { 0 } else { 1 }, exact.distance);
+        }
+    }
+
+    #[test]
+    fn dna_ambiguous_pattern() {
+        let text = "acgtacgtnnacgatcgatcgatgcatgcatcgatcgtagctagctagctagcatcgatcgatcgatcgagctagctagcatcgatcgattagcatagctagctagcatcgactacgatcgatgactagcatttagc";
+        let mut options = FuzzySearchOptions::new(0);
+        options.alphabet = Alphabet::Dna {
+            text_ambiguity: false,
+        };
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::literal("NNRGCA"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(5, matches.len());
+        assert!(matches
+            .iter()
+            .all(|m| m.match_text.ends_with("agca") || m.match_text.ends_with("ggca")));
+        assert_match(89, 95, text, &matches[2]);
+    }
+
+    #[test]
+    fn ignore_diacritics() {
+        let text = "---------------------------------------------------------------------------------------------Malmö--Malmo\u{308}--Malme";
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignore_diacritics = true;
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::literal("Malmo"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(3, matches.len());
+        assert_eq!("Malmö", matches[0].match_text);
+        assert_eq!(0, matches[0].distance);
+        assert_eq!("Malmo\u{308}", matches[1].match_text);
+        assert_eq!((100, 106), (matches[1].start_index, matches[1].end_index));
+        assert_eq!("Malme", matches[2].match_text);
+        assert_eq!(1, matches[2].distance);
+    }
+
+    #[test]
+    fn word_boundary() {
+        let text = "art department, arts and smart art. ".repeat(4);
+        let mut options = FuzzySearchOptions::new(1);
+        options.word_boundary = crate::fuzzy_search_options::WordBoundary::Both;
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::literal("art"),
+            &text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        let expected = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("art"),
+            &text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(8, matches.len());
+        assert_eq!(
+            expected.iter().map(|m| m.start_index).collect::<Vec<_>>(),
+            matches.iter().map(|m| m.start_index).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn search_mode() {
+        let text = "2O23-".to_string() + &"-".repeat(100) + "-2024";
+        let mut options = FuzzySearchOptions::new(1);
+        let find = |text: &str, options: &FuzzySearchOptions| {
+            FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+                &Pattern::literal("2023"),
+                text,
+                options,
+            )
+            .unwrap()
+            .map(|m| m.start_index)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![0, 106], find(&text, &options));
+
+        options.search_mode = SearchMode::Prefix;
+        assert_eq!(vec![0], find(&text, &options));
+
+        options.search_mode = SearchMode::Suffix;
+        assert_eq!(vec![106], find(&text, &options));
+
+        options.search_mode = SearchMode::Whole;
+        assert!(find(&text, &options).is_empty());
+        assert_eq!(vec![0], find("2024", &options));
+    }
+
+    #[test]
+    fn pattern_longer_than_text() {
+        assert_eq!(
+            0,
+            FuzzySearchSubstitutionsOnlySimdTest::find("foo", "fo", 1).count()
+        );
+    }
+
+    #[test]
+    fn char_matcher() {
+        let text = "INV-OO42-2O23 ".repeat(10);
+        let mut options = FuzzySearchOptions::new(0);
+        options.char_matcher = Some(Arc::new(EquivalenceMatcher::look_alikes()));
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::literal("INV-0042-2023"),
+            &text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        let expected = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("INV-0042-2023"),
+            &text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(10, matches.len());
+        assert_eq!(
+            expected.iter().map(|m| m.start_index).collect::<Vec<_>>(),
+            matches.iter().map(|m| m.start_index).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn case_folding() {
+        let text = "Die STRASSE und die Straße--------------------------------------------------------------------------------------------------strasse";
+        let mut options = FuzzySearchOptions::new(0);
+        options.case_folding = crate::fuzzy_search_options::CaseFolding::Full;
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::literal("straße"),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(3, matches.len());
+        assert_eq!((4, 11), (matches[0].start_index, matches[0].end_index));
+        assert_eq!("STRASSE", matches[0].match_text);
+        assert_eq!((20, 26), (matches[1].start_index, matches[1].end_index));
+        assert_eq!("Straße", matches[1].match_text);
+        assert_eq!("strasse", matches[2].match_text);
+    }
+
+    #[test]
+    fn case_folding_class() {
+        let text = "-----------------------------------------------------------------------------------------------------------------ab-AB-aB";
+        let mut options = FuzzySearchOptions::new(0);
+        options.case_folding = crate::fuzzy_search_options::CaseFolding::Simple;
+
+        let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+            &Pattern::parse("[A-B]b").unwrap(),
+            text,
+            &options,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        assert_eq!(3, matches.len());
+        assert_match(113, 115, text, &matches[0]);
+        assert_match(116, 118, text, &matches[1]);
+        assert_match(119, 121, text, &matches[2]);
+    }
+
+    #[test]
+    fn simd_levels() {
+        let text = "--patxexn--pattern--PATTERN--paxxern-pattern--".repeat(20) + "pattern";
+        let mut options = FuzzySearchOptions::new(2);
+        options.word_boundary = crate::fuzzy_search_options::WordBoundary::Start;
+
+        let expected = FuzzySearchSubstitutionsOnly::find_with_options(
+            &Pattern::literal("pattern"),
+            &text,
+            &options,
+        )
+        .unwrap()
+        .map(|m| (m.start_index, m.distance))
+        .collect::<Vec<_>>();
+
+        let levels = [
+            SimdLevel::Scalar,
+            SimdLevel::Portable,
+            SimdLevel::Sse42,
+            SimdLevel::Avx2,
+            SimdLevel::Avx512,
+        ];
+
+        for level in levels.into_iter().filter(|l| l.is_supported()) {
+            let matches = FuzzySearchSubstitutionsOnlySimdTest::find_with_options(
+                &Pattern::literal("pattern"),
+                &text,
+                &options,
+            )
+            .unwrap()
+            .with_simd_level(level)
+            .map(|m| (m.start_index, m.distance))
+            .collect::<Vec<_>>();
+
+            assert_eq!(expected, matches, "{level:?}");
+        }
+
+        assert_eq!(81, expected.len());
+    }
+
+    #[test]
+    fn gap_pattern() {
+        let pattern = Pattern::parse("Dear *{0,10},").unwrap();
+        let search = FuzzySearchSubstitutionsOnlySimdTest::find_pattern(&pattern, "Dear John,", 1);
+
+        assert!(matches!(search, Err(PatternError::GapsNotSupported)));
+    }
+
     #[test]
     fn no_match() {
         let pattern = "foo";
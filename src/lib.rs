@@ -1,32 +1,133 @@
 #![feature(portable_simd)]
 
-use fuzzy_search_levenshtein::FuzzySearchLevenshtein;
+use std::borrow::Cow;
+
+use candidate_match::{CandidateMatch, EDIT_COST};
+use candidates::Candidates;
 use fuzzy_search_options::FuzzySearchOptions;
 use match_consolidator::MatchConsolidator;
 use match_result::MatchResult;
+use normalized_text::{NormalizedText, OffsetMap};
+use pattern::Pattern;
 
 mod candidate_match;
+mod candidates;
+mod fuzzy_search_banded;
+mod fuzzy_search_bitap;
 mod fuzzy_search_levenshtein;
+mod fuzzy_search_levenshtein_simd;
+mod fuzzy_search_myers;
 mod match_consolidator;
+mod normalized_text;
+mod parallel_search;
+mod prefilter;
 
+pub mod char_class;
+pub mod char_matcher;
+pub mod fuzzy_search_graphemes;
 pub mod fuzzy_search_options;
 pub mod fuzzy_search_substitutions_only;
+pub mod fuzzy_search_substitutions_only_bytes;
+pub mod fuzzy_search_substitutions_only_multi;
 pub mod fuzzy_search_substitutions_only_simd;
 pub mod match_result;
+pub mod multi_pattern_search;
+pub mod nucleotide;
+pub mod pattern;
+pub mod simd_level;
 
 pub struct FuzzySearch<'a> {
-    consolidated_matches: MatchConsolidator<FuzzySearchLevenshtein<'a>>,
+    consolidated_matches: MatchConsolidator<Candidates<'a>>,
     text_chars: &'a [char],
+    offsets: Option<OffsetMap>, // set if the text was normalized, maps match indices back to the original text
 }
 
 impl<'a> FuzzySearch<'a> {
     pub fn find(pattern: &str, text: &'a [char], options: &'a FuzzySearchOptions) -> Self {
+        Self::find_pattern(&Pattern::literal(pattern), text, options)
+    }
+
+    /// Search using a parsed pattern, see [`Pattern::parse`] for the wildcard syntax.
+    /// See [`FuzzySearchOptions::validate_engine`] for engines which do not support the pattern or options
+    pub fn find_pattern(
+        pattern: &Pattern,
+        text: &'a [char],
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        Self::search(pattern, text, None, options)
+    }
+
+    /// Search where substitutions and insertions cost less at low confidence text positions, eg from OCR or sequencing.
+    /// Confidence is between 0 and 1 for each text char, a substitution of a char with confidence 0.5 adds 0.5 to the weighted distance.
+    /// Deletions always have full cost. The weighted distance is used for the limits and for picking the best match
+    ///
+    /// # Panics
+    /// If the confidence and text lengths differ
+    pub fn find_weighted(
+        pattern: &Pattern,
+        text: &'a [char],
+        confidence: &[f32],
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        assert_eq!(
+            text.len(),
+            confidence.len(),
+            "confidence is required for each text char"
+        );
+
+        Self::search(pattern, text, Some(confidence), options)
+    }
+
+    /// Same matches as [`FuzzySearch::find_pattern`], but parts of the text are searched on up to the given number of threads.
+    /// Worth it for long texts, short texts and searches anchored at the start are searched on the current thread
+    pub fn find_parallel(
+        pattern: &Pattern,
+        text: &[char],
+        options: &FuzzySearchOptions,
+        threads: usize,
+    ) -> Vec<MatchResult> {
+        parallel_search::find_parallel(pattern, text, options, threads)
+    }
+
+    fn search(
+        pattern: &Pattern,
+        text: &'a [char],
+        confidence: Option<&[f32]>,
+        options: &'a FuzzySearchOptions,
+    ) -> Self {
+        let (normalized_text, offsets) = match NormalizedText::normalize(text, options) {
+            Some(normalized) => (Cow::Owned(normalized.chars), Some(normalized.offsets)),
+            None => (Cow::Borrowed(text), None),
+        };
+
+        let edit_cost = |c: f32| (c.clamp(0.0, 1.0) * EDIT_COST as f32).round() as u32;
+        let edit_costs = confidence.map(|confidence| match &offsets {
+            Some(offsets) => offsets
+                .ranges()
+                .iter()
+                .map(|(start, end)| {
+                    confidence[*start..*end]
+                        .iter()
+                        .map(|c| edit_cost(*c))
+                        .min()
+                        .unwrap_or(EDIT_COST)
+                })
+                .collect(),
+            None => confidence.iter().map(|c| edit_cost(*c)).collect(),
+        });
+
         Self {
             text_chars: text,
             consolidated_matches: MatchConsolidator::consolidate(
                 options.max_total_distance,
-                FuzzySearchLevenshtein::find(pattern, text, options),
+                Candidates::find(
+                    pattern,
+                    normalized_text,
+                    edit_costs.map(Cow::Owned),
+                    options,
+                ),
             ),
+            offsets,
         }
     }
 }
@@ -35,23 +136,48 @@ impl<'a> Iterator for FuzzySearch<'a> {
     type Item = MatchResult;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.consolidated_matches.next().map(|m| MatchResult {
-            start_index: m.start_index,
-            end_index: m.text_index,
-            distance: m.distance,
-            match_text: self.text_chars[m.start_index..m.text_index]
-                .iter()
-                .collect::<String>(),
-            deletions: m.deletions,
-            substitutions: m.substitutions,
-            insertions: m.insertions,
-        })
+        self.consolidated_matches
+            .next()
+            .map(|m| match_result(m, self.text_chars, self.offsets.as_ref()))
+    }
+}
+
+/// Result for a match in the normalized text, with indexes and match text from the original text
+fn match_result(
+    m: CandidateMatch,
+    text_chars: &[char],
+    offsets: Option<&OffsetMap>,
+) -> MatchResult {
+    let (start_index, end_index) = match offsets {
+        Some(offsets) => offsets.original_range(m.start_index, m.text_index),
+        None => (m.start_index, m.text_index),
+    };
+
+    MatchResult {
+        start_index,
+        end_index,
+        distance: m.distance,
+        weighted_distance: m.costs.weighted_distance(),
+        match_text: text_chars[start_index..end_index]
+            .iter()
+            .collect::<String>(),
+        deletions: m.deletions,
+        substitutions: m.substitutions,
+        insertions: m.insertions,
+        ignored: m.ignored,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
+    use crate::fuzzy_search_options::{
+        Alphabet, CaseFolding, EditDensity, Engine, Prefilter, SearchMode, UnsupportedEngine,
+        WordBoundary,
+    };
+    use crate::{char_class::CharClass, char_matcher::EquivalenceMatcher};
 
     #[test]
     fn test_pattern_pattern_with_grapheme() {
@@ -834,6 +960,951 @@ mod tests {
         run_test_other("abcd", "xc", 0, "xc", 3, 3);
     }
 
+    #[test]
+    fn test_wildcard_any() {
+        let pattern = Pattern::parse("INV-????-2023").unwrap();
+        let text = "--INV-0042-2023--INV-123-2O23--"
+            .chars()
+            .collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(2);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 2, "INV-0042-2023", 0);
+        assert_match(&results[1], 17, "INV-123-2O23", 2);
+    }
+
+    #[test]
+    fn test_wildcard_gap() {
+        let pattern = Pattern::parse("Dear *{0,10},").unwrap();
+        let text = "Dear John, and Dear Jane Doe, and Dear Bartholomew Jr,"
+            .chars()
+            .collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(0);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 0, "Dear John,", 0);
+        assert_match(&results[1], 15, "Dear Jane Doe,", 0);
+    }
+
+    #[test]
+    fn test_wildcard_gap_with_errors() {
+        let pattern = Pattern::parse("Dear *{0,10},").unwrap();
+        let text = "Deer John,".chars().collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(1);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 0, "Deer John,", 1);
+    }
+
+    #[test]
+    fn test_escaped_wildcard() {
+        let pattern = Pattern::parse(r"why\?").unwrap();
+        let text = "why! why?".chars().collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(0);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 5, "why?", 0);
+    }
+
+    #[test]
+    fn test_char_classes() {
+        let pattern = Pattern::parse(r"total: \d\d[.,]\d\d").unwrap();
+        let text = "total: 12.50 total: l2,5O total: ab.cd"
+            .chars()
+            .collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(2);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 0, "total: 12.50", 0);
+        assert_match(&results[1], 13, "total: l2,5O", 2);
+    }
+
+    #[test]
+    fn test_char_class_ocr_confusions() {
+        let pattern = Pattern::parse("[0-9OIl][0-9OIl][0-9OIl]-[0-9OIl][0-9OIl]").unwrap();
+        let text = "call 1O4-2l now".chars().collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(0);
+
+        let results = FuzzySearch::find_pattern(&pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 5, "1O4-2l", 0);
+    }
+
+    #[test]
+    fn test_dna_iupac_codes() {
+        let text = "gatcgatcgattagcatagctagcaaacgatcg"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.alphabet = Alphabet::Dna {
+            text_ambiguity: false,
+        };
+
+        let results = FuzzySearch::find("TTRGCWTNGC", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 10, "ttagcatagc", 0);
+
+        let results = FuzzySearch::find("TTRGCWNNGC", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 10, "ttagcatagc", 0);
+    }
+
+    #[test]
+    fn test_dna_text_ambiguity() {
+        let text = "gatcgatcgattngcatagctagcaaacgatcg"
+            .chars()
+            .collect::<Vec<_>>();
+        let pattern = "TTAGCATAG";
+
+        let mut options = FuzzySearchOptions::new(0);
+        options.alphabet = Alphabet::Dna {
+            text_ambiguity: false,
+        };
+
+        assert_eq!(FuzzySearch::find(pattern, &text, &options).count(), 0);
+
+        options.alphabet = Alphabet::Dna {
+            text_ambiguity: true,
+        };
+
+        let results = FuzzySearch::find(pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 10, "ttngcatag", 0);
+    }
+
+    #[test]
+    fn test_dna_insertion() {
+        let text = "gatcgatcgattagcxatagctagc".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.alphabet = Alphabet::Dna {
+            text_ambiguity: false,
+        };
+
+        let results = FuzzySearch::find("TTRGCATNG", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 10, "ttagcxatag", 1);
+    }
+
+    #[test]
+    fn test_case_folding_simple() {
+        let text = "Über die BRÜCKE".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Simple;
+
+        let results = FuzzySearch::find("brücke", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 9, "BRÜCKE", 0);
+
+        options.case_folding = CaseFolding::None;
+
+        assert_eq!(FuzzySearch::find("brücke", &text, &options).count(), 0);
+    }
+
+    #[test]
+    fn test_case_folding_full_offsets() {
+        // ß folds to ss, so folded text is longer than the original text
+        let text = "Die Straße und die STRASSE".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+
+        let results = FuzzySearch::find("strasse", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 4, "Straße", 0);
+        assert_eq!(results[0].end_index, 10);
+        assert_match(&results[1], 19, "STRASSE", 0);
+        assert_eq!(results[1].end_index, 26);
+    }
+
+    #[test]
+    fn test_case_folding_full_dotted_i() {
+        // İ folds to i followed by a combining dot
+        let text = "-- İSTANBUL --".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+
+        let results = FuzzySearch::find("istanbul", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 3, "İSTANBUL", 1);
+        assert_eq!(results[0].end_index, 11);
+    }
+
+    #[test]
+    fn test_ignore_diacritics() {
+        let text = "Malmo, Malmö and Malmo\u{308}".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignore_diacritics = true;
+
+        let results = FuzzySearch::find("Malmö", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 3);
+        assert_match(&results[0], 0, "Malmo", 0);
+        assert_match(&results[1], 7, "Malmö", 0);
+        assert_match(&results[2], 17, "Malmo\u{308}", 0);
+        assert_eq!(results[2].end_index, 23);
+    }
+
+    #[test]
+    fn test_ignore_diacritics_with_errors() {
+        let text = "her RESUMME was".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignore_diacritics = true;
+        options.case_folding = CaseFolding::Simple;
+
+        let results = FuzzySearch::find("résumé", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 4, "RESUMME", 1);
+    }
+
+    #[test]
+    fn test_multiple_separate_matches() {
+        run_test("Malmo", "Malmo, Malmo and Malmo", 1, 0, "Malmo", 0, 3);
+    }
+
+    #[test]
+    fn test_layout_tolerant_line_break() {
+        let text = "the infor-\nmation was lost".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+
+        assert_eq!(FuzzySearch::find("information", &text, &options).count(), 0);
+
+        options.layout_tolerant = true;
+        let results = FuzzySearch::find("information", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 4, "infor-\nmation", 0);
+    }
+
+    #[test]
+    fn test_layout_tolerant_spaces() {
+        let text = "the in formation  was\n lost".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(0);
+        options.layout_tolerant = true;
+
+        let results = FuzzySearch::find("information", &text, &options).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 4, "in formation", 0);
+
+        let results = FuzzySearch::find("was lost", &text, &options).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 18, "was\n lost", 0);
+
+        let results = FuzzySearch::find("the in for mation", &text, &options).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 0, "the in formation", 0);
+    }
+
+    #[test]
+    fn test_word_boundary() {
+        let text = "the department, art, arts and smart at"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.word_boundary = WordBoundary::Both;
+
+        let results = FuzzySearch::find("art", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 3);
+        assert_match(&results[0], 16, "art", 0);
+        assert_match(&results[1], 21, "arts", 1);
+        assert_match(&results[2], 36, "at", 1);
+    }
+
+    #[test]
+    fn test_word_boundary_start() {
+        let text = "the department of arts and the smart art"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(0);
+        options.word_boundary = WordBoundary::Start;
+
+        let results = FuzzySearch::find("art", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 18, "art", 0);
+        assert_match(&results[1], 37, "art", 0);
+    }
+
+    #[test]
+    fn test_search_mode_prefix() {
+        let text = "Xpatern and pattern".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(2);
+        options.search_mode = SearchMode::Prefix;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 0, "Xpatern", 2);
+        assert_eq!(results[0].insertions, 1);
+        assert_eq!(results[0].deletions, 1);
+    }
+
+    #[test]
+    fn test_search_mode_suffix() {
+        let text = "pattern and patern".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.search_mode = SearchMode::Suffix;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 12, "patern", 1);
+    }
+
+    #[test]
+    fn test_search_mode_whole() {
+        let mut options = FuzzySearchOptions::new(1);
+        options.search_mode = SearchMode::Whole;
+
+        let find = |text: &str, options: &FuzzySearchOptions| {
+            FuzzySearch::find("pattern", &text.chars().collect::<Vec<_>>(), options)
+                .map(|m| (m.start_index, m.end_index, m.distance))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![(0, 7, 0)], find("pattern", &options));
+        assert_eq!(vec![(0, 8, 1)], find("Xpattern", &options));
+        assert_eq!(vec![(0, 8, 1)], find("patternX", &options));
+        assert_eq!(vec![(0, 6, 1)], find("patern", &options));
+        assert!(find("a pattern", &options).is_empty());
+
+        options.max_insertions = 0;
+        assert!(find("Xpattern", &options).is_empty());
+    }
+
+    #[test]
+    fn test_search_mode_empty_text() {
+        let find = |pattern: &str, options: &FuzzySearchOptions| {
+            FuzzySearch::find(pattern, &[], options)
+                .map(|m| (m.start_index, m.end_index, m.distance, m.deletions))
+                .collect::<Vec<_>>()
+        };
+
+        for search_mode in [SearchMode::Prefix, SearchMode::Suffix, SearchMode::Whole] {
+            for engine in [
+                Engine::CandidateStack,
+                Engine::Myers,
+                Engine::Banded,
+                Engine::Bitap,
+                Engine::Simd,
+            ] {
+                let mut options = FuzzySearchOptions::new(2);
+                options.search_mode = search_mode;
+                options.engine = engine;
+
+                assert_eq!(vec![(0, 0, 1, 1)], find("b", &options));
+                assert_eq!(vec![(0, 0, 2, 2)], find("ab", &options));
+                assert!(find("abc", &options).is_empty());
+
+                options.max_deletions = 1;
+                assert!(find("ab", &options).is_empty());
+            }
+        }
+
+        let mut options = FuzzySearchOptions::new(2);
+        assert!(find("b", &options).is_empty());
+
+        options.search_mode = SearchMode::Whole;
+        let find_pattern = |pattern: &str| {
+            FuzzySearch::find_pattern(&Pattern::parse(pattern).unwrap(), &[], &options)
+                .map(|m| (m.start_index, m.end_index, m.distance))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![(0, 0, 2)], find_pattern("a*{0,3}b"));
+        assert!(find_pattern("a*{1,3}b").is_empty());
+    }
+
+    #[test]
+    fn test_char_matcher() {
+        let text = "Invoice NO. I0O42, total".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.char_matcher = Some(Arc::new(EquivalenceMatcher::look_alikes()));
+
+        let results = FuzzySearch::find("1OO42", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 12, "I0O42", 0);
+    }
+
+    #[test]
+    fn test_char_matcher_closure() {
+        let text = "the quick brewn fox".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(0);
+        options.char_matcher = Some(Arc::new(|p: char, t: char| {
+            p == t || ("aeiou".contains(p) && "aeiou".contains(t))
+        }));
+
+        let results = FuzzySearch::find("bruwn", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 10, "brewn", 0);
+    }
+
+    #[test]
+    fn test_ignorable_chars() {
+        let text = "the in.voi,ce and invoice".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignorable_chars = Some(CharClass::from_chars(".,\u{200B}"));
+
+        let results = FuzzySearch::find("invoice", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 2);
+        assert_match(&results[0], 4, "in.voi,ce", 0);
+        assert_eq!(results[0].ignored, 2);
+        assert_eq!(results[0].insertions, 0);
+        assert_match(&results[1], 18, "invoice", 0);
+        assert_eq!(results[1].ignored, 0);
+    }
+
+    #[test]
+    fn test_ignorable_chars_not_at_match_boundaries() {
+        let text = "..in\u{200B}voice..".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(0);
+        options.ignorable_chars = Some(CharClass::from_chars(".\u{200B}"));
+
+        let results = FuzzySearch::find("invoice", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 2, "in\u{200B}voice", 0);
+        assert_eq!(results[0].end_index, 10);
+
+        options.ignorable_chars = None;
+        assert_eq!(FuzzySearch::find("invoice", &text, &options).count(), 0);
+    }
+
+    #[test]
+    fn test_weighted_low_confidence() {
+        let text = "the patxxrn here".chars().collect::<Vec<_>>();
+        let mut confidence = vec![1.0; text.len()];
+        confidence[7] = 0.25;
+        confidence[8] = 0.5;
+        let options = FuzzySearchOptions::new(1);
+
+        assert_eq!(FuzzySearch::find("pattern", &text, &options).count(), 0);
+
+        let results =
+            FuzzySearch::find_weighted(&Pattern::literal("pattern"), &text, &confidence, &options)
+                .collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 4, "patxxrn", 2);
+        assert_eq!(results[0].substitutions, 2);
+        assert_eq!(results[0].weighted_distance, 0.75);
+    }
+
+    #[test]
+    fn test_weighted_ranking() {
+        // both candidates have one substitution, the one at the low confidence position is preferred
+        let text = "pbttern".chars().collect::<Vec<_>>();
+        let confidence = [1.0, 0.1, 1.0, 1.0, 1.0, 1.0, 1.0];
+        let options = FuzzySearchOptions::new(1);
+
+        let results =
+            FuzzySearch::find_weighted(&Pattern::literal("pattern"), &text, &confidence, &options)
+                .collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 0, "pbttern", 1);
+        assert!((results[0].weighted_distance - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_weighted_full_confidence() {
+        let text = "--patxern--".chars().collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(2);
+
+        let results = FuzzySearch::find_weighted(
+            &Pattern::literal("pattern"),
+            &text,
+            &vec![1.0; text.len()],
+            &options,
+        )
+        .collect::<Vec<_>>();
+
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 2, "patxern", 1);
+        assert_eq!(results[0].weighted_distance, 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_weighted_confidence_length() {
+        let text = "pattern".chars().collect::<Vec<_>>();
+        let options = FuzzySearchOptions::new(2);
+
+        FuzzySearch::find_weighted(&Pattern::literal("pattern"), &text, &[1.0], &options);
+    }
+
+    #[test]
+    fn test_edit_density() {
+        let pattern = "the quick brown fox jumps over the lazy dog";
+        let spread = "the quack brown fox jumps ovr the lazy dug"
+            .chars()
+            .collect::<Vec<_>>();
+        let clustered = "the quick brown fox jmpz over the lazy dog"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(3);
+
+        assert_eq!(FuzzySearch::find(pattern, &spread, &options).count(), 1);
+        assert_eq!(FuzzySearch::find(pattern, &clustered, &options).count(), 1);
+
+        options.edit_density = Some(EditDensity {
+            max_edits: 1,
+            window: 5,
+        });
+
+        let results = FuzzySearch::find(pattern, &spread, &options).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].distance, 3);
+        assert_eq!(FuzzySearch::find(pattern, &clustered, &options).count(), 0);
+    }
+
+    #[test]
+    fn test_myers_engine() {
+        let text = "---abcc----abc---axc--".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        let summary = |options: &FuzzySearchOptions| {
+            FuzzySearch::find("abc", &text, options)
+                .map(|r| (r.start_index, r.end_index, r.distance))
+                .collect::<Vec<_>>()
+        };
+        let expected = summary(&options);
+
+        options.engine = Engine::Myers;
+
+        assert_eq!(expected, summary(&options));
+    }
+
+    #[test]
+    fn test_myers_engine_long_pattern() {
+        let pattern = "the quick brown fox jumps over the lazy dog and keeps running far away";
+        let text = "some text before the quick brown fox jumps ovr the lazy dog and keeps runing far away. the end"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(2);
+        options.engine = Engine::Myers;
+
+        let results = FuzzySearch::find(pattern, &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!(17, results[0].start_index);
+        assert_eq!(2, results[0].distance);
+        assert_eq!(2, results[0].deletions);
+    }
+
+    #[test]
+    fn test_myers_engine_limits() {
+        let text = "---patxern---".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.engine = Engine::Myers;
+
+        assert_eq!(1, FuzzySearch::find("pattern", &text, &options).count());
+
+        options.max_substitutions = 0;
+
+        assert_eq!(0, FuzzySearch::find("pattern", &text, &options).count());
+    }
+
+    #[test]
+    fn test_myers_engine_deletion_limit() {
+        // the alignment with the fewest edits deletes the c, but other ones within the limits exist
+        let text = "baaacbacaba".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.max_deletions = 0;
+        options.engine = Engine::Myers;
+
+        let results = FuzzySearch::find("c", &text, &options)
+            .map(|r| (r.start_index, r.end_index))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(4, 5), (7, 8), (9, 10)], results);
+    }
+
+    /// Compares the engine with the candidate stack on deterministic pseudo random searches,
+    /// with random limits, search modes and word boundaries
+    fn assert_same_as_candidate_stack(engine: Engine, max_pattern_length: usize) {
+        let mut state = engine as u64;
+        let mut random = |n: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+
+        for case in 0..2000 {
+            let pattern = (0..1 + random(max_pattern_length))
+                .map(|_| ['a', 'b', 'c', 'd'][random(4)])
+                .collect::<String>();
+            let text = (0..random(50))
+                .map(|_| ['a', 'b', 'c', 'd', ' '][random(5)])
+                .collect::<Vec<_>>();
+
+            let max_distance = random(4);
+            let mut options = FuzzySearchOptions::new(max_distance);
+            options.max_substitutions = random(max_distance + 2).min(max_distance);
+            options.max_deletions = random(max_distance + 2).min(max_distance);
+            options.max_insertions = random(max_distance + 2).min(max_distance);
+            options.search_mode = [
+                SearchMode::Anywhere,
+                SearchMode::Anywhere,
+                SearchMode::Prefix,
+                SearchMode::Suffix,
+                SearchMode::Whole,
+            ][random(5)];
+            options.word_boundary =
+                [WordBoundary::None, WordBoundary::None, WordBoundary::Both][random(3)];
+
+            let summary = |options: &FuzzySearchOptions| {
+                FuzzySearch::find(&pattern, &text, options)
+                    .map(|r| {
+                        (
+                            r.start_index,
+                            r.end_index,
+                            r.substitutions,
+                            r.deletions,
+                            r.insertions,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let expected = summary(&options);
+
+            options.engine = engine;
+
+            assert_eq!(
+                expected,
+                summary(&options),
+                "case {case}: {pattern:?} in {:?}, limits {:?}, {:?} {:?}",
+                text.iter().collect::<String>(),
+                (
+                    max_distance,
+                    options.max_substitutions,
+                    options.max_deletions,
+                    options.max_insertions
+                ),
+                options.search_mode,
+                options.word_boundary,
+            );
+        }
+    }
+
+    #[test]
+    fn test_myers_engine_random() {
+        assert_same_as_candidate_stack(Engine::Myers, 8);
+    }
+
+    #[test]
+    fn test_myers_engine_unsupported() {
+        let text = "pattern".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.engine = Engine::Myers;
+        options.edit_density = Some(EditDensity {
+            max_edits: 1,
+            window: 5,
+        });
+
+        let error = options
+            .validate_engine(&Pattern::literal("pattern"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "edit_density is not supported by the Myers engine"
+        );
+
+        // the candidate stack searches instead
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_match(&results[0], 0, "pattern", 0);
+    }
+
+    #[test]
+    fn test_banded_engine() {
+        let text = "---abcc----abc---axc--ab-c".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        let summary = |options: &FuzzySearchOptions| {
+            FuzzySearch::find("abc", &text, options)
+                .map(|r| (r.start_index, r.end_index, r.distance))
+                .collect::<Vec<_>>()
+        };
+        let expected = summary(&options);
+
+        options.engine = Engine::Banded;
+
+        assert_eq!(expected, summary(&options));
+    }
+
+    #[test]
+    fn test_banded_engine_limits() {
+        let text = "the patxern here".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(2);
+        options.engine = Engine::Banded;
+        options.max_substitutions = 0;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_eq!("patxern", results[0].match_text);
+        assert_eq!(0, results[0].substitutions);
+        assert_eq!(1, results[0].deletions);
+        assert_eq!(1, results[0].insertions);
+    }
+
+    #[test]
+    fn test_banded_engine_substitution_limit() {
+        // the alignment with the fewest edits in each dp cell is not the one within the substitution limit
+        let text = "baabcbcdcdaddadabdcbabacdcbbddcdcaaaddaddadbabbb"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(2);
+        options.max_substitutions = 1;
+        let summary = |options: &FuzzySearchOptions| {
+            FuzzySearch::find("adddaadad", &text, options)
+                .map(|r| (r.start_index, r.end_index, r.substitutions, r.deletions))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![(35, 43, 1, 1)], summary(&options));
+
+        for engine in [Engine::Banded, Engine::Auto] {
+            options.engine = engine;
+            assert_eq!(vec![(35, 43, 1, 1)], summary(&options), "{engine:?}");
+        }
+    }
+
+    #[test]
+    fn test_banded_engine_random() {
+        assert_same_as_candidate_stack(Engine::Banded, 8);
+        assert_same_as_candidate_stack(Engine::Auto, 8);
+    }
+
+    #[test]
+    fn test_banded_engine_prefix() {
+        let text = "patern and more".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.engine = Engine::Banded;
+        options.search_mode = SearchMode::Prefix;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_match(&results[0], 0, "patern", 1);
+    }
+
+    #[test]
+    fn test_bitap_engine() {
+        let text = "---abcc----abc---axc--ab-c".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        let summary = |options: &FuzzySearchOptions| {
+            FuzzySearch::find("abc", &text, options)
+                .map(|r| (r.start_index, r.end_index, r.distance))
+                .collect::<Vec<_>>()
+        };
+        let expected = summary(&options);
+
+        options.engine = Engine::Bitap;
+        assert_eq!(expected, summary(&options));
+
+        options.engine = Engine::Auto;
+        assert_eq!(expected, summary(&options));
+    }
+
+    #[test]
+    fn test_bitap_engine_substitutions_only() {
+        let text = "the pattxrn and patern".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::with_limits(1, None, Some(0), Some(0));
+        options.engine = Engine::Bitap;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(1, results.len());
+        assert_match(&results[0], 4, "pattxrn", 1);
+    }
+
+    #[test]
+    fn test_bitap_engine_whole() {
+        // the insertions make the distance larger than the pattern
+        let text = "cbc".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(2);
+        options.engine = Engine::Bitap;
+        options.search_mode = SearchMode::Whole;
+
+        let results = FuzzySearch::find("c", &text, &options)
+            .map(|r| (r.start_index, r.end_index, r.distance))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(0, 3, 2)], results);
+    }
+
+    #[test]
+    fn test_bitap_engine_random() {
+        assert_same_as_candidate_stack(Engine::Bitap, 8);
+    }
+
+    #[test]
+    fn test_bitap_engine_long_pattern() {
+        let pattern = "pattern".repeat(10);
+        let text = pattern.chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.engine = Engine::Bitap;
+
+        let error = options
+            .validate_engine(&Pattern::literal(&pattern))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            UnsupportedEngine {
+                engine: Engine::Bitap,
+                feature: "a pattern longer than 64 chars"
+            }
+        );
+
+        options.max_total_distance = 0;
+        assert_eq!(FuzzySearch::find(&pattern, &text, &options).count(), 1);
+
+        options.engine = Engine::Auto;
+        assert_eq!(
+            options.validate_engine(&Pattern::literal(&pattern)),
+            Ok(Engine::Myers)
+        );
+    }
+
+    #[test]
+    fn test_exact_seeds_prefilter() {
+        let text = "lorem ipsum constitutional dolor sit conftitufional amet, consectetur constitutionally adipiscing constittuional elit"
+            .repeat(20)
+            .chars()
+            .collect::<Vec<_>>();
+
+        for engine in [
+            Engine::CandidateStack,
+            Engine::Myers,
+            Engine::Banded,
+            Engine::Bitap,
+            Engine::Simd,
+        ] {
+            let mut options = FuzzySearchOptions::new(3);
+            options.engine = engine;
+
+            let summary = |options: &FuzzySearchOptions| {
+                FuzzySearch::find("constitutional", &text, options)
+                    .map(|r| (r.start_index, r.end_index, r.distance))
+                    .collect::<Vec<_>>()
+            };
+            let expected = summary(&options);
+
+            options.prefilter = Prefilter::ExactSeeds;
+
+            assert_eq!(80, expected.len());
+            assert_eq!(expected, summary(&options), "{engine:?}");
+        }
+    }
+
+    #[test]
+    fn test_exact_seeds_prefilter_word_boundary() {
+        let text = "the patterns and a pattxrn".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.word_boundary = WordBoundary::Both;
+        options.prefilter = Prefilter::ExactSeeds;
+
+        let results = FuzzySearch::find("pattern", &text, &options).collect::<Vec<_>>();
+
+        assert_eq!(2, results.len());
+        assert_match(&results[0], 4, "patterns", 1);
+        assert_match(&results[1], 19, "pattxrn", 1);
+    }
+
+    #[test]
+    fn test_qgram_prefilter() {
+        let dna = "gatcctccatatacaacggtatctccacctcaggtttagatctcaacaacggaaccattgccgacatgagacagttaggtatcgtcgagagttacaagctaaaacgagcagtagtcagctctgcatctgaagccgctgaagttctactaagggtggataacatcatccgtgcaagaccaagaaccgccaatagacaacatatgtaacatatttaggatatacctcgaaaataataaaccgccacactgtcattattataattagaaacagaacgcaaaaattatccactatataattcaaagacgcgaaaaaaaaagaacaacgcgtcatagaacttttggcaattcgcgtcacaaataaattttggcaacttatgtttcctcttcgagcagtactcgagccctgtctcaagaatgtaataatacccatcgtaggtatggttaaagatagcatctccacaacctcaaagctccttgccgagagtcgccctcctttgtcgagtaattttcacttttcatatgagaacttattttcttattctttactctcacatcctgtagtgattgacactgcaacagccaccatcactagaagaacagaacaattacttaatagaaaaattatatcttcctcgaaacgatttcctgcttccaacatctacgtatatcaagaagcattcacttaccatgacacagcttcagatttcattattgctgacagctactatatcactactccatctagtagtggccacgccctatgaggcatatcctatcggaaaacaataccccccagtggcaagagtcaatgaatcgtttacatttcaaatttccaatgatacctataaatcgtctgtagacaagacagctcaaataacatacaattgcttcgacttaccgagctggctttcgtttgactctagttctagaacgttctcaggtgaaactacttgtctttgactgtgagcattgcgactgttggctaactccagtaacgacataatatcggaatatcttgcgcatgcgataaatacgcctacttgcagcacacgctgtttagtaatcataaaaaaactatcgcttttagggggggttactagtcgagaataaaaaatcaaattttgtcacttttaatcactatgtgaaacgaaatcgctcagacttataagggaagtatctcgatctaagttggattttctagtttt"
+            .chars()
+            .collect::<Vec<_>>();
+        let text = "lorem ipsum constitutional dolor sit conftitufional amet, consectetur constitutionally adipiscing constittuional elit"
+            .repeat(20)
+            .chars()
+            .collect::<Vec<_>>();
+
+        for (pattern, text, q) in [
+            ("constitutional", &text, None),
+            ("constitutional", &text, Some(2)),
+            ("ggtatctcgatctaagttggattttctag", &dna, None),
+            ("aacatatttaggatatacctggaaaataat", &dna, Some(3)),
+        ] {
+            for engine in [Engine::CandidateStack, Engine::Myers, Engine::Simd] {
+                let mut options = FuzzySearchOptions::new(3);
+                options.engine = engine;
+
+                let summary = |options: &FuzzySearchOptions| {
+                    FuzzySearch::find(pattern, text, options)
+                        .map(|r| (r.start_index, r.end_index, r.distance))
+                        .collect::<Vec<_>>()
+                };
+                let expected = summary(&options);
+
+                options.prefilter = Prefilter::QGrams { q };
+
+                assert!(!expected.is_empty());
+                assert_eq!(expected, summary(&options), "{pattern} {q:?} {engine:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_simd_engine() {
+        let text = "---abcc----abc---axc--ab-c"
+            .repeat(30)
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        let summary = |options: &FuzzySearchOptions| {
+            FuzzySearch::find("abc", &text, options)
+                .map(|r| (r.start_index, r.end_index, r.distance))
+                .collect::<Vec<_>>()
+        };
+        let expected = summary(&options);
+
+        options.engine = Engine::Simd;
+
+        assert_eq!(expected, summary(&options));
+    }
+
+    #[test]
+    fn test_simd_engine_limits() {
+        let text = "the patxern here".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.engine = Engine::Simd;
+
+        assert_eq!(1, FuzzySearch::find("pattern", &text, &options).count());
+
+        options.max_substitutions = 0;
+
+        assert_eq!(0, FuzzySearch::find("pattern", &text, &options).count());
+    }
+
+    #[test]
+    fn test_simd_engine_random() {
+        assert_same_as_candidate_stack(Engine::Simd, 8);
+    }
+
     #[test]
     fn test_iterator() {
         let text = "---abcc----abc---axc--";
@@ -6,7 +6,7 @@ pub struct MatchConsolidator<TIterator: Iterator<Item = CandidateMatch>> {
     group: Vec<CandidateMatch>,
 }
 
-impl<'a, TIterator: Iterator<Item = CandidateMatch>> MatchConsolidator<TIterator> {
+impl<TIterator: Iterator<Item = CandidateMatch>> MatchConsolidator<TIterator> {
     pub fn consolidate(max_distance: usize, matches: TIterator) -> Self {
         Self {
             matches,
@@ -16,11 +16,11 @@ impl<'a, TIterator: Iterator<Item = CandidateMatch>> MatchConsolidator<TIterator
     }
 
     #[inline(always)]
-    fn get_best_match_from_group(group: &Vec<CandidateMatch>) -> CandidateMatch {
+    fn get_best_match_from_group(group: &[CandidateMatch]) -> CandidateMatch {
         group
             .iter()
             .min_by(|a, b| {
-                a.distance.cmp(&b.distance).then_with(|| {
+                a.costs.total.cmp(&b.costs.total).then_with(|| {
                     (b.text_index - b.start_index).cmp(&(a.text_index - a.start_index))
                 })
             })
@@ -29,34 +29,31 @@ impl<'a, TIterator: Iterator<Item = CandidateMatch>> MatchConsolidator<TIterator
     }
 }
 
-impl<'a, TIterator: Iterator<Item = CandidateMatch>> Iterator for MatchConsolidator<TIterator> {
+impl<TIterator: Iterator<Item = CandidateMatch>> Iterator for MatchConsolidator<TIterator> {
     type Item = CandidateMatch;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(first_match) = self.matches.next() {
-            self.group.push(first_match);
-
-            while let Some(next_match) = self.matches.next() {
-                let match_start_index = &self.group.last().unwrap().start_index; // hmm.. unwrap...
-                if next_match.start_index > (match_start_index + self.max_distance) {
-                    let best_match = Self::get_best_match_from_group(&self.group);
-
-                    self.group.clear();
-                    self.group.push(next_match);
+        // the group may already contain the first match of the next group from the previous call
+        if self.group.is_empty() {
+            self.group.push(self.matches.next()?);
+        }
 
-                    return Some(best_match);
-                }
+        for next_match in self.matches.by_ref() {
+            let match_start_index = &self.group.last().unwrap().start_index; // hmm.. unwrap...
+            if next_match.start_index > (match_start_index + self.max_distance) {
+                let best_match = Self::get_best_match_from_group(&self.group);
 
+                self.group.clear();
                 self.group.push(next_match);
+
+                return Some(best_match);
             }
-        }
 
-        if !self.group.is_empty() {
-            let best_match = Self::get_best_match_from_group(&self.group);
-            self.group.clear();
-            return Some(best_match);
+            self.group.push(next_match);
         }
 
-        None
+        let best_match = Self::get_best_match_from_group(&self.group);
+        self.group.clear();
+        Some(best_match)
     }
 }
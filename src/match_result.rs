@@ -3,8 +3,43 @@ pub struct MatchResult {
     pub start_index: usize,
     pub end_index: usize,
     pub distance: usize,
+    /// Distance where edits at low confidence text positions cost less, equal to distance for unweighted searches
+    pub weighted_distance: f32,
     pub match_text: String,
     pub deletions: usize,
     pub substitutions: usize,
     pub insertions: usize,
+    /// Ignorable text chars skipped inside the match, these dont count towards the distance
+    pub ignored: usize,
+}
+
+/// Closest of many equal length patterns at a text position, with substitutions only
+#[derive(Debug)]
+pub struct ClosestPatternMatch {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub match_text: String,
+    /// Index of the closest pattern, the lowest one if several are equally close
+    pub pattern_id: usize,
+    pub distance: usize,
+    /// Other patterns at the same distance, empty if the closest pattern is unambiguous
+    pub tied_pattern_ids: Vec<usize>,
+}
+
+/// Match found when searching by grapheme clusters, indexes are in graphemes and bytes
+#[derive(Debug)]
+pub struct GraphemeMatchResult {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub distance: usize,
+    /// Distance where edits at low confidence text positions cost less, equal to distance for unweighted searches
+    pub weighted_distance: f32,
+    pub match_text: String,
+    pub deletions: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    /// Ignorable text chars skipped inside the match, these dont count towards the distance
+    pub ignored: usize,
 }
@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+
+use crate::{
+    candidates::Candidates, fuzzy_search_options::FuzzySearchOptions,
+    match_consolidator::MatchConsolidator, match_result::MatchResult,
+    normalized_text::NormalizedText, pattern::Pattern, prefilter,
+};
+
+/// Searches a text for many patterns at once, each with its own options.
+/// The text is normalized once for each distinct normalization in the options, and scanned once for the exact seeds of all patterns.
+/// The engines then only search around the seeds of each pattern.
+///
+/// Patterns without usable seeds are not part of the shared scan, they are each searched over the whole text on their own.
+/// That is the case for patterns with wildcards, classes or nucleotide codes, a distance of at least the pattern length,
+/// or seeds shorter than 3 chars. The text is only scanned once if every pattern has seeds
+#[derive(Default)]
+pub struct MultiPatternSearch {
+    patterns: Vec<(Pattern, FuzzySearchOptions)>,
+}
+
+impl MultiPatternSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pattern, returns the id its matches are tagged with
+    pub fn add(&mut self, pattern: Pattern, options: FuzzySearchOptions) -> usize {
+        self.patterns.push((pattern, options));
+        self.patterns.len() - 1
+    }
+
+    /// Matches of all patterns with the id of the pattern, ordered by start index and pattern id.
+    /// Matches are consolidated for each pattern like in [`crate::FuzzySearch`], overlapping matches of different patterns are all returned
+    pub fn find(&self, text: &[char]) -> Vec<(usize, MatchResult)> {
+        let mut results = Vec::new();
+        let mut remaining = (0..self.patterns.len()).collect::<Vec<_>>();
+
+        while let Some(first) = remaining.first() {
+            let settings = NormalizedText::settings(&self.patterns[*first].1);
+            let (group, rest): (Vec<usize>, Vec<usize>) = remaining
+                .iter()
+                .partition(|i| NormalizedText::settings(&self.patterns[**i].1) == settings);
+
+            self.find_group(&group, text, &mut results);
+            remaining = rest;
+        }
+
+        results.sort_by_key(|(id, m)| (m.start_index, *id));
+        results
+    }
+
+    /// Searches for patterns which share the normalized text
+    fn find_group(&self, group: &[usize], text: &[char], results: &mut Vec<(usize, MatchResult)>) {
+        let (normalized_text, offsets) =
+            match NormalizedText::normalize(text, &self.patterns[group[0]].1) {
+                Some(normalized) => (Cow::Owned(normalized.chars), Some(normalized.offsets)),
+                None => (Cow::Borrowed(text), None),
+            };
+
+        let prepared = group
+            .iter()
+            .map(|i| self.patterns[*i].0.prepare(&self.patterns[*i].1))
+            .collect::<Vec<_>>();
+
+        let windows = prefilter::shared_seed_windows(
+            &prepared
+                .iter()
+                .zip(group)
+                .map(|(pattern, i)| (pattern.elements(), &self.patterns[*i].1))
+                .collect::<Vec<_>>(),
+            &normalized_text,
+        );
+
+        for (i, windows) in group.iter().zip(windows) {
+            let (pattern, options) = &self.patterns[*i];
+            let text_chars = Cow::Borrowed(&*normalized_text);
+
+            let candidates = match windows {
+                Some(windows) => {
+                    Candidates::find_in_windows(pattern, text_chars, None, options, Some(windows))
+                }
+                None => Candidates::find(pattern, text_chars, None, options),
+            };
+
+            results.extend(
+                MatchConsolidator::consolidate(options.max_total_distance, candidates)
+                    .map(|m| (*i, crate::match_result(m, text, offsets.as_ref()))),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod multi_pattern_search_tests {
+    use super::*;
+    use crate::{
+        fuzzy_search_options::{CaseFolding, Engine},
+        FuzzySearch,
+    };
+
+    fn summary(m: &MatchResult) -> (usize, usize, usize, String) {
+        (m.start_index, m.end_index, m.distance, m.match_text.clone())
+    }
+
+    #[test]
+    fn test_same_as_separate_searches() {
+        let text = "Invoice INV-0042-2O23 for Jonh Smith, amount 1234.50 EUR. Paid by JOHN SMITH on 2023-05-01, ref INV-0043-2023. "
+            .repeat(20)
+            .chars()
+            .collect::<Vec<_>>();
+
+        let patterns = || {
+            let mut folded = FuzzySearchOptions::new(1);
+            folded.case_folding = CaseFolding::Simple;
+            let mut myers = FuzzySearchOptions::new(2);
+            myers.engine = Engine::Myers;
+
+            vec![
+                (Pattern::literal("John Smith"), FuzzySearchOptions::new(2)),
+                (Pattern::literal("john smith"), folded),
+                (
+                    Pattern::parse("INV-????-2023").unwrap(),
+                    FuzzySearchOptions::new(1),
+                ),
+                (Pattern::literal("1234.50"), myers),
+                (Pattern::literal("EUR"), FuzzySearchOptions::new(1)),
+                (
+                    Pattern::literal("nothing like it"),
+                    FuzzySearchOptions::new(2),
+                ),
+            ]
+        };
+
+        let mut search = MultiPatternSearch::new();
+        for (pattern, options) in patterns() {
+            search.add(pattern, options);
+        }
+
+        let results = search.find(&text);
+
+        for (id, (pattern, options)) in patterns().iter().enumerate() {
+            let expected = FuzzySearch::find_pattern(pattern, &text, options)
+                .map(|m| summary(&m))
+                .collect::<Vec<_>>();
+            let actual = results
+                .iter()
+                .filter(|(i, _)| *i == id)
+                .map(|(_, m)| summary(m))
+                .collect::<Vec<_>>();
+
+            assert_eq!(expected, actual, "pattern {id}");
+        }
+
+        assert_eq!(0, results.iter().filter(|(id, _)| *id == 5).count());
+        // the swapped chars in Jonh are 2 edits, case folding with 1 edit only finds JOHN SMITH
+        assert_eq!(20, results.iter().filter(|(id, _)| *id == 0).count());
+        assert_eq!(20, results.iter().filter(|(id, _)| *id == 1).count());
+    }
+
+    #[test]
+    fn test_ids_and_order() {
+        let text = "abcdef xyz abcdef".chars().collect::<Vec<_>>();
+
+        let mut search = MultiPatternSearch::new();
+        assert_eq!(
+            0,
+            search.add(Pattern::literal("xyz"), FuzzySearchOptions::new(0))
+        );
+        assert_eq!(
+            1,
+            search.add(Pattern::literal("abcdef"), FuzzySearchOptions::new(1))
+        );
+        assert_eq!(
+            2,
+            search.add(Pattern::literal("abc"), FuzzySearchOptions::new(0))
+        );
+
+        let results = search
+            .find(&text)
+            .into_iter()
+            .map(|(id, m)| (id, m.start_index))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(1, 0), (2, 0), (0, 7), (1, 11), (2, 11)], results);
+    }
+
+    #[test]
+    fn test_patterns_without_seeds() {
+        let text = "order 4711 shipped, order 4712 pending, ordr 47l1 lost"
+            .chars()
+            .collect::<Vec<_>>();
+        let patterns = || {
+            vec![
+                (Pattern::literal("order 4711"), FuzzySearchOptions::new(2)),
+                (
+                    Pattern::parse(r"\d\d\d\d").unwrap(),
+                    FuzzySearchOptions::new(1),
+                ),
+                (Pattern::literal("ab"), FuzzySearchOptions::new(1)),
+                (Pattern::literal("4711"), FuzzySearchOptions::new(4)),
+            ]
+        };
+
+        let separate = patterns();
+        let prepared = separate
+            .iter()
+            .map(|(p, options)| (p.prepare(options), options))
+            .collect::<Vec<_>>();
+        let windows = prefilter::shared_seed_windows(
+            &prepared
+                .iter()
+                .map(|(p, options)| (p.elements(), *options))
+                .collect::<Vec<_>>(),
+            &text,
+        );
+
+        // only the first pattern is part of the shared scan, a class, short seeds and a distance of the pattern length rule out the others
+        assert_eq!(
+            vec![true, false, false, false],
+            windows.iter().map(Option::is_some).collect::<Vec<_>>()
+        );
+
+        let mut search = MultiPatternSearch::new();
+        for (pattern, options) in patterns() {
+            search.add(pattern, options);
+        }
+        let results = search.find(&text);
+
+        for (id, (pattern, options)) in separate.iter().enumerate() {
+            let expected = FuzzySearch::find_pattern(pattern, &text, options)
+                .map(|m| summary(&m))
+                .collect::<Vec<_>>();
+            let actual = results
+                .iter()
+                .filter(|(i, _)| *i == id)
+                .map(|(_, m)| summary(m))
+                .collect::<Vec<_>>();
+
+            assert_eq!(expected, actual, "pattern {id}");
+        }
+    }
+
+    #[test]
+    fn test_no_patterns() {
+        let text = "some text".chars().collect::<Vec<_>>();
+
+        assert!(MultiPatternSearch::new().find(&text).is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+use crate::fuzzy_search_options::{CaseFolding, FuzzySearchOptions};
+
+/// Maps indices in normalized text back to the original text
+pub(crate) struct OffsetMap {
+    ranges: Vec<(usize, usize)>, // range in the original text each normalized char was produced from
+    original_length: usize,
+}
+
+impl OffsetMap {
+    pub fn original_range(&self, start_index: usize, end_index: usize) -> (usize, usize) {
+        let start = self
+            .ranges
+            .get(start_index)
+            .map_or(self.original_length, |r| r.0);
+
+        let end = match end_index {
+            0 => start,
+            end_index => self.ranges[end_index - 1].1.max(start),
+        };
+
+        (start, end)
+    }
+
+    /// Range in the original text of each normalized char
+    pub fn ranges(&self) -> &[(usize, usize)] {
+        &self.ranges
+    }
+}
+
+pub(crate) struct NormalizedText {
+    pub chars: Vec<char>,
+    pub offsets: OffsetMap,
+}
+
+impl NormalizedText {
+    /// Normalizes the text according to the options, or returns None if the options dont require any normalization
+    pub fn normalize(text: &[char], options: &FuzzySearchOptions) -> Option<Self> {
+        if !Self::is_required(options) {
+            return None;
+        }
+
+        let mut normalized = text
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (*c, i, i + 1))
+            .collect::<Vec<_>>();
+
+        if options.case_folding != CaseFolding::None {
+            normalized = expand(normalized, |c| fold_case(c, options.case_folding));
+        }
+
+        if options.ignore_diacritics {
+            normalized = expand(normalized, strip_diacritics);
+        }
+
+        if options.layout_tolerant {
+            normalized = normalize_layout(normalized);
+        }
+
+        Some(NormalizedText {
+            chars: normalized.iter().map(|n| n.0).collect(),
+            offsets: OffsetMap {
+                ranges: normalized.iter().map(|n| (n.1, n.2)).collect(),
+                original_length: text.len(),
+            },
+        })
+    }
+
+    /// The options which affect the normalized text, texts normalized with equal settings are identical
+    pub fn settings(options: &FuzzySearchOptions) -> (CaseFolding, bool, bool) {
+        (
+            options.case_folding,
+            options.ignore_diacritics,
+            options.layout_tolerant,
+        )
+    }
+
+    pub fn is_required(options: &FuzzySearchOptions) -> bool {
+        options.case_folding != CaseFolding::None
+            || options.ignore_diacritics
+            || options.layout_tolerant
+    }
+}
+
+/// Replaces each char with the chars produced by f, keeping track of the original range.
+/// If a char is removed, its range is added to the previous char so matches dont end in the middle of eg a combining sequence
+fn expand<I: Iterator<Item = char>>(
+    normalized: Vec<(char, usize, usize)>,
+    f: impl Fn(char) -> I,
+) -> Vec<(char, usize, usize)> {
+    let mut expanded: Vec<(char, usize, usize)> = Vec::with_capacity(normalized.len());
+
+    for (c, start, end) in normalized {
+        let length = expanded.len();
+        expanded.extend(f(c).map(|e| (e, start, end)));
+
+        if expanded.len() == length {
+            if let Some(previous) = expanded.last_mut() {
+                previous.2 = end;
+            }
+        }
+    }
+
+    expanded
+}
+
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// Collapses runs of whitespace into a single space and removes soft hyphens and hyphens followed by a line break,
+/// the range of removed chars is added to the previous char
+fn normalize_layout(normalized: Vec<(char, usize, usize)>) -> Vec<(char, usize, usize)> {
+    let mut result: Vec<(char, usize, usize)> = Vec::with_capacity(normalized.len());
+    let mut index = 0;
+
+    while index < normalized.len() {
+        let (c, start, _) = normalized[index];
+        let whitespace_end = normalized[index + 1..]
+            .iter()
+            .position(|n| !n.0.is_whitespace())
+            .map_or(normalized.len(), |p| index + 1 + p);
+        let line_break_follows = normalized[index + 1..whitespace_end]
+            .iter()
+            .any(|n| is_line_break(n.0));
+
+        if c == SOFT_HYPHEN || (is_hyphen(c) && line_break_follows) {
+            let end = match line_break_follows {
+                true => whitespace_end,
+                false => index + 1,
+            };
+
+            if let Some(previous) = result.last_mut() {
+                previous.2 = normalized[end - 1].2;
+            }
+
+            index = end;
+        } else if c.is_whitespace() {
+            let end = normalized[index..]
+                .iter()
+                .position(|n| !n.0.is_whitespace())
+                .map_or(normalized.len(), |p| index + p);
+
+            result.push((' ', start, normalized[end - 1].2));
+            index = end;
+        } else {
+            result.push(normalized[index]);
+            index += 1;
+        }
+    }
+
+    result
+}
+
+fn is_hyphen(c: char) -> bool {
+    matches!(c, '-' | '\u{2010}' | SOFT_HYPHEN)
+}
+
+fn is_line_break(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// Removes combining marks from the canonical decomposition of a char, eg é -> e and a combining acute accent -> nothing.
+/// Chars which decompose without marks, like hangul syllables, are left as is
+pub(crate) fn strip_diacritics(c: char) -> impl Iterator<Item = char> {
+    let mut decomposed = Vec::with_capacity(4);
+    decompose_canonical(c, |d| decomposed.push(d));
+
+    if decomposed.iter().any(|d| is_combining_mark(*d)) {
+        decomposed.retain(|d| !is_combining_mark(*d));
+    } else {
+        decomposed = vec![c];
+    }
+
+    decomposed.into_iter()
+}
+
+/// Folds the case of a char, full folding can produce multiple chars, eg ß -> ss
+pub(crate) fn fold_case(c: char, case_folding: CaseFolding) -> impl Iterator<Item = char> {
+    // lowercasing the uppercase takes care of most special cases in full case folding, eg ß -> SS -> ss and ς -> Σ -> σ
+    // the first lowercase handles chars like ẞ which uppercase to themselves
+    let full = || {
+        c.to_lowercase()
+            .flat_map(char::to_uppercase)
+            .flat_map(char::to_lowercase)
+    };
+
+    let folded: Vec<char> = match case_folding {
+        CaseFolding::None => vec![c],
+        CaseFolding::Full => full().collect(),
+        CaseFolding::Simple => {
+            let mut full = full();
+            let mut lowercase = c.to_lowercase();
+
+            match (full.next(), full.next(), lowercase.next(), lowercase.next()) {
+                (Some(f), None, _, _) => vec![f],
+                (_, _, Some(l), None) => vec![l],
+                _ => vec![c],
+            }
+        }
+    };
+
+    folded.into_iter()
+}
+
+#[cfg(test)]
+mod normalized_text_tests {
+    use super::*;
+
+    fn fold(text: &str, case_folding: CaseFolding) -> String {
+        text.chars()
+            .flat_map(|c| fold_case(c, case_folding))
+            .collect()
+    }
+
+    #[test]
+    fn test_fold_case_simple() {
+        assert_eq!("straße", fold("STRAßE", CaseFolding::Simple));
+        assert_eq!("ß", fold("ẞ", CaseFolding::Simple));
+        assert_eq!("σσ", fold("Σς", CaseFolding::Simple));
+        assert_eq!("İ", fold("İ", CaseFolding::Simple));
+    }
+
+    #[test]
+    fn test_fold_case_full() {
+        assert_eq!("strasse", fold("STRAßE", CaseFolding::Full));
+        assert_eq!("ss", fold("ẞ", CaseFolding::Full));
+        assert_eq!("i\u{307}", fold("İ", CaseFolding::Full));
+        assert_eq!("fi", fold("ﬁ", CaseFolding::Full));
+    }
+
+    #[test]
+    fn test_offsets() {
+        let text = "Straße!".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+
+        let normalized = NormalizedText::normalize(&text, &options).unwrap();
+
+        assert_eq!("strasse!", normalized.chars.iter().collect::<String>());
+        assert_eq!((0, 7), normalized.offsets.original_range(0, 8));
+        assert_eq!((4, 5), normalized.offsets.original_range(4, 5));
+        assert_eq!((4, 5), normalized.offsets.original_range(5, 6));
+        assert_eq!((5, 6), normalized.offsets.original_range(6, 7));
+        assert_eq!((7, 7), normalized.offsets.original_range(8, 8));
+    }
+
+    #[test]
+    fn test_strip_diacritics() {
+        let strip = |text: &str| text.chars().flat_map(strip_diacritics).collect::<String>();
+
+        assert_eq!("Malmo", strip("Malmö"));
+        assert_eq!("resume", strip("résumé"));
+        assert_eq!("resume", strip("re\u{301}sume\u{301}"));
+        assert_eq!("Angstrom", strip("Ångström"));
+        assert_eq!("한국", strip("한국"));
+    }
+
+    #[test]
+    fn test_diacritics_offsets() {
+        // combining marks are removed, and their range is added to the preceding char
+        let text = "re\u{301}sume\u{301}!".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.ignore_diacritics = true;
+
+        let normalized = NormalizedText::normalize(&text, &options).unwrap();
+
+        assert_eq!("resume!", normalized.chars.iter().collect::<String>());
+        assert_eq!((0, 8), normalized.offsets.original_range(0, 6));
+        assert_eq!((1, 3), normalized.offsets.original_range(1, 2));
+        assert_eq!((8, 9), normalized.offsets.original_range(6, 7));
+    }
+
+    #[test]
+    fn test_diacritics_and_case_folding() {
+        let text = "İSTANBUL".chars().collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.case_folding = CaseFolding::Full;
+        options.ignore_diacritics = true;
+
+        let normalized = NormalizedText::normalize(&text, &options).unwrap();
+
+        assert_eq!("istanbul", normalized.chars.iter().collect::<String>());
+        assert_eq!((0, 1), normalized.offsets.original_range(0, 1));
+    }
+
+    #[test]
+    fn test_layout() {
+        let text = "infor-\nmation  and\tsoft\u{AD}hy-\r\n  phens - ok"
+            .chars()
+            .collect::<Vec<_>>();
+        let mut options = FuzzySearchOptions::new(1);
+        options.layout_tolerant = true;
+
+        let normalized = NormalizedText::normalize(&text, &options).unwrap();
+
+        assert_eq!(
+            "information and softhyphens - ok",
+            normalized.chars.iter().collect::<String>()
+        );
+        assert_eq!((0, 13), normalized.offsets.original_range(0, 11));
+        assert_eq!((13, 15), normalized.offsets.original_range(11, 12));
+        assert_eq!((19, 24), normalized.offsets.original_range(16, 20));
+    }
+
+    #[test]
+    fn test_not_required() {
+        let text = "Straße".chars().collect::<Vec<_>>();
+
+        assert!(NormalizedText::normalize(&text, &FuzzySearchOptions::new(1)).is_none());
+    }
+}
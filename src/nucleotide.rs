@@ -0,0 +1,70 @@
+pub const A: u8 = 0b0001;
+pub const C: u8 = 0b0010;
+pub const G: u8 = 0b0100;
+pub const T: u8 = 0b1000;
+
+/// Bitmask of the bases an IUPAC nucleotide code can represent, or 0 if the char is not a nucleotide code
+#[inline(always)]
+pub fn iupac_mask(c: char) -> u8 {
+    match c.to_ascii_uppercase() {
+        'A' => A,
+        'C' => C,
+        'G' => G,
+        'T' | 'U' => T,
+        'R' => A | G,
+        'Y' => C | T,
+        'S' => C | G,
+        'W' => A | T,
+        'K' => G | T,
+        'M' => A | C,
+        'B' => C | G | T,
+        'D' => A | G | T,
+        'H' => A | C | T,
+        'V' => A | C | G,
+        'N' => A | C | G | T,
+        _ => 0,
+    }
+}
+
+/// Bitmask of an unambiguous base, or 0 if the char is not one of ACGTU
+#[inline(always)]
+pub fn base_mask(c: char) -> u8 {
+    match c.to_ascii_uppercase() {
+        'A' => A,
+        'C' => C,
+        'G' => G,
+        'T' | 'U' => T,
+        _ => 0,
+    }
+}
+
+/// Mask of a text char, ambiguity codes in the text are only expanded if `text_ambiguity` is set
+#[inline(always)]
+pub fn text_mask(c: char, text_ambiguity: bool) -> u8 {
+    match text_ambiguity {
+        true => iupac_mask(c),
+        false => base_mask(c),
+    }
+}
+
+#[cfg(test)]
+mod nucleotide_tests {
+    use super::*;
+
+    #[test]
+    fn test_iupac_mask() {
+        assert_eq!(A | G, iupac_mask('R'));
+        assert_eq!(A | G, iupac_mask('r'));
+        assert_eq!(T, iupac_mask('U'));
+        assert_eq!(A | C | G | T, iupac_mask('N'));
+        assert_eq!(0, iupac_mask('-'));
+        assert_eq!(0, iupac_mask('X'));
+    }
+
+    #[test]
+    fn test_text_mask() {
+        assert_eq!(C, text_mask('c', false));
+        assert_eq!(0, text_mask('N', false));
+        assert_eq!(A | C | G | T, text_mask('N', true));
+    }
+}
@@ -0,0 +1,216 @@
+use std::{borrow::Cow, iter, thread};
+
+use crate::{
+    candidates::Candidates,
+    fuzzy_search_options::FuzzySearchOptions,
+    match_consolidator::MatchConsolidator,
+    match_result::MatchResult,
+    normalized_text::NormalizedText,
+    pattern::Pattern,
+    prefilter::{self, Windows},
+    FuzzySearch,
+};
+
+/// Searches parts of the text on scoped threads, with the same matches as the sequential search.
+/// Each part only starts matches inside it, parts starting right after an exact match are searched again afterwards.
+/// The candidates of all parts are consolidated together, so matches at the seams are merged like anywhere else
+pub(crate) fn find_parallel(
+    pattern: &Pattern,
+    text: &[char],
+    options: &FuzzySearchOptions,
+    threads: usize,
+) -> Vec<MatchResult> {
+    let prepared = pattern.prepare(options);
+    let longest_match = prepared.len() + options.max_total_distance;
+    let parts = threads.min(text.len() / (4 * longest_match).max(1)).max(1);
+
+    if parts == 1 || prepared.is_empty() || options.is_anchored_start() {
+        return FuzzySearch::find_pattern(pattern, text, options).collect();
+    }
+
+    let (normalized_text, offsets) = match NormalizedText::normalize(text, options) {
+        Some(normalized) => (Cow::Owned(normalized.chars), Some(normalized.offsets)),
+        None => (Cow::Borrowed(text), None),
+    };
+    let text_chars = &*normalized_text;
+    let length = text_chars.len();
+
+    let start_windows = prefilter::start_windows(prepared.elements(), text_chars, false, options)
+        .unwrap_or_else(|| Windows::new(iter::once(0..length).collect()));
+
+    let bounds = (0..=parts).map(|i| i * length / parts).collect::<Vec<_>>();
+    let part = |from: usize, to: usize, exact_match: Option<usize>| {
+        let candidates = Candidates::find_in_windows(
+            pattern,
+            Cow::Borrowed(text_chars),
+            None,
+            options,
+            Some(start_windows.restricted(from..to)),
+        );
+
+        match exact_match {
+            Some(start_index) => candidates.after_exact_match(start_index).collect(),
+            None => candidates.collect::<Vec<_>>(),
+        }
+    };
+
+    let part = &part;
+    let mut part_candidates = thread::scope(|scope| {
+        let handles = bounds
+            .windows(2)
+            .map(|bounds| scope.spawn(move || part(bounds[0], bounds[1], None)))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("part search panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    // after an exact match the candidate stack only searches the next start for exact matches, it may lie in a later part
+    let mut exact_match = None;
+
+    for (candidates, range) in part_candidates
+        .iter_mut()
+        .zip(bounds.windows(2).map(|bounds| bounds[0]..bounds[1]))
+    {
+        if let Some(start_index) = exact_match {
+            if range.contains(&next_start(text_chars, options, start_index + 1)) {
+                *candidates = part(range.start, range.end, exact_match);
+            }
+        }
+
+        if let Some(exact) = candidates.iter().rev().find(|c| c.distance == 0) {
+            exact_match = Some(exact.start_index);
+        }
+    }
+
+    MatchConsolidator::consolidate(
+        options.max_total_distance,
+        part_candidates.into_iter().flatten(),
+    )
+    .map(|m| crate::match_result(m, text, offsets.as_ref()))
+    .collect()
+}
+
+/// First allowed match start at or after the index, like the candidate stack picks its next start without windows
+fn next_start(text: &[char], options: &FuzzySearchOptions, index: usize) -> usize {
+    (index..text.len())
+        .find(|&i| options.allows_match_start(text, i))
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod parallel_search_tests {
+    use super::*;
+    use crate::fuzzy_search_options::{CaseFolding, Engine, Prefilter, WordBoundary};
+
+    const ENGINES: [Engine; 6] = [
+        Engine::CandidateStack,
+        Engine::Myers,
+        Engine::Banded,
+        Engine::Bitap,
+        Engine::Simd,
+        Engine::Auto,
+    ];
+
+    fn summary(m: &MatchResult) -> (usize, usize, usize, String) {
+        (m.start_index, m.end_index, m.distance, m.match_text.clone())
+    }
+
+    fn assert_same_as_sequential(pattern: &Pattern, text: &str, options: &FuzzySearchOptions) {
+        let text = text.chars().collect::<Vec<_>>();
+        let expected = FuzzySearch::find_pattern(pattern, &text, options)
+            .map(|m| summary(&m))
+            .collect::<Vec<_>>();
+
+        for threads in [1, 2, 3, 7, 16] {
+            let actual = find_parallel(pattern, &text, options, threads)
+                .iter()
+                .map(summary)
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                expected, actual,
+                "{:?} engine with {threads} threads",
+                options.engine
+            );
+        }
+    }
+
+    fn assert_same_for_engines(
+        pattern: &str,
+        text: &str,
+        options: impl Fn() -> FuzzySearchOptions,
+    ) {
+        for engine in ENGINES {
+            let mut options = options();
+            options.engine = engine;
+            assert_same_as_sequential(&Pattern::literal(pattern), text, &options);
+        }
+    }
+
+    #[test]
+    fn test_same_as_sequential() {
+        let text = "The goverment of the United Kingdom, the goverement and the govermnet, governments and govern ment. "
+            .repeat(12);
+
+        for k in 0..4 {
+            assert_same_for_engines("government", &text, || FuzzySearchOptions::new(k));
+        }
+    }
+
+    #[test]
+    fn test_periodic_text() {
+        // exact matches at every index, the candidate stack skips the start after each of them
+        let text = "a".repeat(301);
+
+        for k in 0..3 {
+            assert_same_for_engines("aaaa", &text, || FuzzySearchOptions::new(k));
+        }
+
+        let text = "ab".repeat(150);
+
+        for k in 0..3 {
+            assert_same_for_engines("abab", &text, || FuzzySearchOptions::new(k));
+        }
+    }
+
+    #[test]
+    fn test_word_boundaries_and_prefilter() {
+        let text = "pattern patterns apattern pattren pat tern patern ".repeat(15);
+
+        assert_same_for_engines("pattern", &text, || {
+            let mut options = FuzzySearchOptions::new(2);
+            options.word_boundary = WordBoundary::Both;
+            options
+        });
+        assert_same_for_engines("pattern", &text, || {
+            let mut options = FuzzySearchOptions::new(1);
+            options.prefilter = Prefilter::ExactSeeds;
+            options
+        });
+        assert_same_for_engines("pattern", &text, || {
+            let mut options = FuzzySearchOptions::new(1);
+            options.prefilter = Prefilter::QGrams { q: None };
+            options
+        });
+    }
+
+    #[test]
+    fn test_normalized_text() {
+        let text = "Straße STRASSE strasse Strase ".repeat(20);
+
+        assert_same_for_engines("strasse", &text, || {
+            let mut options = FuzzySearchOptions::new(1);
+            options.case_folding = CaseFolding::Full;
+            options
+        });
+    }
+
+    #[test]
+    fn test_short_text() {
+        assert_same_for_engines("pattern", "patern", || FuzzySearchOptions::new(1));
+        assert_same_for_engines("pattern", "", || FuzzySearchOptions::new(1));
+    }
+}
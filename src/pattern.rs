@@ -0,0 +1,459 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{
+    char_class::CharClass,
+    char_matcher::CharMatcher,
+    fuzzy_search_options::{Alphabet, CaseFolding, FuzzySearchOptions},
+    normalized_text, nucleotide,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternElement {
+    Char(char),
+    /// Matches any single character at no cost
+    Any,
+    /// Matches any character in the class, anything else is a substitution
+    Class(CharClass),
+    /// Matches any base in the mask, see [`Alphabet::Dna`]
+    Nucleotides {
+        mask: u8,
+        text_ambiguity: bool,
+    },
+    /// Skips between `min` and `max` text characters at no cost
+    Gap {
+        min: usize,
+        max: usize,
+    },
+}
+
+impl PatternElement {
+    #[inline(always)]
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            PatternElement::Char(p) => *p == c,
+            PatternElement::Any => true,
+            PatternElement::Class(class) => class.contains(c),
+            PatternElement::Nucleotides {
+                mask,
+                text_ambiguity,
+            } => nucleotide::text_mask(c, *text_ambiguity) & mask != 0,
+            PatternElement::Gap { .. } => false,
+        }
+    }
+
+    /// Same as matches, but chars are compared with the matcher if there is one
+    #[inline(always)]
+    pub fn matches_with(&self, c: char, matcher: Option<&dyn CharMatcher>) -> bool {
+        match (self, matcher) {
+            (PatternElement::Char(p), Some(matcher)) => matcher.matches(*p, c),
+            _ => self.matches(c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    TrailingEscape,
+    UnboundedGap(usize),
+    InvalidGap(usize),
+    GapAtBoundary,
+    UnterminatedClass(usize),
+    InvalidClass(usize),
+    GapsNotSupported,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::TrailingEscape => write!(f, "pattern ends with an escape character"),
+            PatternError::UnboundedGap(i) => {
+                write!(f, "gap at position {i} must have bounds, eg *{{0,3}}")
+            }
+            PatternError::InvalidGap(i) => write!(f, "invalid gap bounds at position {i}"),
+            PatternError::GapAtBoundary => write!(f, "pattern cannot start or end with a gap"),
+            PatternError::UnterminatedClass(i) => {
+                write!(f, "character class at position {i} is missing a closing ]")
+            }
+            PatternError::InvalidClass(i) => write!(f, "invalid character class at position {i}"),
+            PatternError::GapsNotSupported => {
+                write!(
+                    f,
+                    "gaps are not supported when searching with substitutions only"
+                )
+            }
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    elements: Vec<PatternElement>,
+}
+
+impl Pattern {
+    /// Pattern where every character is matched literally
+    pub fn literal(pattern: &str) -> Self {
+        Pattern {
+            elements: pattern.chars().map(PatternElement::Char).collect(),
+        }
+    }
+
+    /// Parses a pattern with wildcards and character classes.
+    /// `?` matches any single character, `*{m,n}` skips between m and n characters and `*{n}` skips exactly n characters.
+    /// `[0-9OIl]` matches any character in the brackets, `[^..]` any character not in them.
+    /// `\d` digit, `\w` word and `\s` whitespace are predefined classes,
+    /// as are the unicode categories `\p{L}`, `\p{Lu}`, `\p{Ll}`, `\p{N}`, `\p{Z}` and `\p{Cc}`. `\P{..}` negates the category.
+    /// Use `\` to escape special characters, eg `\?`, `\[` or `\\`. Any other escaped char, eg `\a`, matches itself
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let mut elements = Vec::new();
+        let mut index = 0;
+
+        while index < chars.len() {
+            match chars[index] {
+                '\\' => {
+                    if let Some(escape) = CharClass::parse_escape(&chars, index + 1) {
+                        let (class, length) = escape?;
+                        elements.push(PatternElement::Class(class));
+                        index += length + 1;
+                    } else {
+                        let escaped = chars.get(index + 1).ok_or(PatternError::TrailingEscape)?;
+                        elements.push(PatternElement::Char(*escaped));
+                        index += 2;
+                    }
+                }
+                '[' => {
+                    let (class, length) = CharClass::parse_bracket(&chars, index)?;
+                    elements.push(PatternElement::Class(class));
+                    index += length;
+                }
+                '?' => {
+                    elements.push(PatternElement::Any);
+                    index += 1;
+                }
+                '*' => {
+                    let (min, max, length) = Self::parse_gap_bounds(&chars, index)?;
+                    elements.push(PatternElement::Gap { min, max });
+                    index += length;
+                }
+                c => {
+                    elements.push(PatternElement::Char(c));
+                    index += 1;
+                }
+            }
+        }
+
+        let starts_or_ends_with_gap = matches!(elements.first(), Some(PatternElement::Gap { .. }))
+            || matches!(elements.last(), Some(PatternElement::Gap { .. }));
+
+        if starts_or_ends_with_gap {
+            return Err(PatternError::GapAtBoundary);
+        }
+
+        Ok(Pattern { elements })
+    }
+
+    /// Returns (min, max, length of the gap syntax in chars)
+    fn parse_gap_bounds(
+        chars: &[char],
+        start: usize,
+    ) -> Result<(usize, usize, usize), PatternError> {
+        if chars.get(start + 1) != Some(&'{') {
+            return Err(PatternError::UnboundedGap(start));
+        }
+
+        let close = chars[start..]
+            .iter()
+            .position(|c| *c == '}')
+            .map(|p| p + start)
+            .ok_or(PatternError::InvalidGap(start))?;
+
+        let bounds = chars[start + 2..close].iter().collect::<String>();
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| PatternError::InvalidGap(start))
+        };
+
+        let (min, max) = match bounds.split_once(',') {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => {
+                let exact = parse(&bounds)?;
+                (exact, exact)
+            }
+        };
+
+        if min > max {
+            return Err(PatternError::InvalidGap(start));
+        }
+
+        Ok((min, max, close - start + 1))
+    }
+
+    /// Applies the normalizations required by the options, the text should be normalized in the same way
+    pub(crate) fn prepare(&self, options: &FuzzySearchOptions) -> Pattern {
+        let pattern = self.with_case_folding(options.case_folding);
+
+        let pattern = match options.ignore_diacritics {
+            true => pattern.without_diacritics(),
+            false => pattern,
+        };
+
+        match options.layout_tolerant {
+            true => pattern.with_collapsed_whitespace(),
+            false => pattern,
+        }
+        .with_alphabet(options.alphabet)
+    }
+
+    /// Interprets the pattern chars according to the alphabet, eg IUPAC codes become nucleotide sets for DNA
+    pub fn with_alphabet(&self, alphabet: Alphabet) -> Pattern {
+        match alphabet {
+            Alphabet::Text => self.clone(),
+            Alphabet::Dna { text_ambiguity } => Pattern {
+                elements: self
+                    .elements
+                    .iter()
+                    .map(|e| match e {
+                        PatternElement::Char(c) if nucleotide::iupac_mask(*c) != 0 => {
+                            PatternElement::Nucleotides {
+                                mask: nucleotide::iupac_mask(*c),
+                                text_ambiguity,
+                            }
+                        }
+                        e => e.clone(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    /// Folds the case of the pattern chars, classes will contain both upper and lowercase variants
+    pub fn with_case_folding(&self, case_folding: CaseFolding) -> Pattern {
+        if case_folding == CaseFolding::None {
+            return self.clone();
+        }
+
+        Pattern {
+            elements: self
+                .elements
+                .iter()
+                .flat_map(|e| match e {
+                    PatternElement::Char(c) => normalized_text::fold_case(*c, case_folding)
+                        .map(PatternElement::Char)
+                        .collect(),
+                    PatternElement::Class(class) => {
+                        vec![PatternElement::Class(class.clone().ignore_case())]
+                    }
+                    e => vec![e.clone()],
+                })
+                .collect(),
+        }
+    }
+
+    /// Removes diacritics from the pattern chars, eg é becomes e
+    pub fn without_diacritics(&self) -> Pattern {
+        Pattern {
+            elements: self
+                .elements
+                .iter()
+                .flat_map(|e| match e {
+                    PatternElement::Char(c) => normalized_text::strip_diacritics(*c)
+                        .map(PatternElement::Char)
+                        .collect(),
+                    e => vec![e.clone()],
+                })
+                .collect(),
+        }
+    }
+
+    /// Collapses runs of whitespace chars into a single space and removes soft hyphens
+    pub fn with_collapsed_whitespace(&self) -> Pattern {
+        let mut elements: Vec<PatternElement> = Vec::with_capacity(self.elements.len());
+
+        for element in &self.elements {
+            match element {
+                PatternElement::Char('\u{AD}') => {}
+                PatternElement::Char(c) if c.is_whitespace() => {
+                    if elements.last() != Some(&PatternElement::Char(' ')) {
+                        elements.push(PatternElement::Char(' '));
+                    }
+                }
+                e => elements.push(e.clone()),
+            }
+        }
+
+        Pattern { elements }
+    }
+
+    pub fn elements(&self) -> &[PatternElement] {
+        &self.elements
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn has_gaps(&self) -> bool {
+        self.elements
+            .iter()
+            .any(|e| matches!(e, PatternElement::Gap { .. }))
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pattern::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use super::*;
+    use crate::char_class::CharCategory;
+    use PatternElement::*;
+
+    #[test]
+    fn test_parse_literal() {
+        let pattern = Pattern::parse("abc").unwrap();
+
+        assert_eq!(pattern, Pattern::literal("abc"));
+    }
+
+    #[test]
+    fn test_parse_any() {
+        let pattern = Pattern::parse("a??c").unwrap();
+
+        assert_eq!(pattern.elements(), &[Char('a'), Any, Any, Char('c')]);
+    }
+
+    #[test]
+    fn test_parse_gap() {
+        let pattern = Pattern::parse("a*{0,3}b*{2}c").unwrap();
+
+        assert_eq!(
+            pattern.elements(),
+            &[
+                Char('a'),
+                Gap { min: 0, max: 3 },
+                Char('b'),
+                Gap { min: 2, max: 2 },
+                Char('c')
+            ]
+        );
+        assert!(pattern.has_gaps());
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let pattern = Pattern::parse(r"a\?\*\\").unwrap();
+
+        assert_eq!(
+            pattern.elements(),
+            &[Char('a'), Char('?'), Char('*'), Char('\\')]
+        );
+        assert!(!pattern.has_gaps());
+
+        // only \d, \w, \s and \p{..} are classes, other escaped letters stay literals
+        let pattern = Pattern::parse(r"\a\u\l").unwrap();
+        assert_eq!(pattern.elements(), &[Char('a'), Char('u'), Char('l')]);
+    }
+
+    #[test]
+    fn test_parse_classes() {
+        let pattern = Pattern::parse(r"[0-9OIl]\d\p{Lu}\[").unwrap();
+
+        assert_eq!(
+            pattern.elements(),
+            &[
+                Class(
+                    CharClass::parse_bracket(&['[', '0', '-', '9', 'O', 'I', 'l', ']'], 0)
+                        .unwrap()
+                        .0
+                ),
+                Class(CharClass::from_category(CharCategory::Digit)),
+                Class(CharClass::from_category(CharCategory::Uppercase)),
+                Char('[')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_alphabet_dna() {
+        let pattern = Pattern::parse("AcN-?")
+            .unwrap()
+            .with_alphabet(Alphabet::Dna {
+                text_ambiguity: false,
+            });
+
+        assert_eq!(
+            pattern.elements(),
+            &[
+                Nucleotides {
+                    mask: nucleotide::A,
+                    text_ambiguity: false
+                },
+                Nucleotides {
+                    mask: nucleotide::C,
+                    text_ambiguity: false
+                },
+                Nucleotides {
+                    mask: nucleotide::A | nucleotide::C | nucleotide::G | nucleotide::T,
+                    text_ambiguity: false
+                },
+                Char('-'),
+                Any
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_case_folding() {
+        let pattern = Pattern::parse("Aß[A-Z]").unwrap();
+
+        assert_eq!(
+            pattern.with_case_folding(CaseFolding::Full).elements(),
+            &[
+                Char('a'),
+                Char('s'),
+                Char('s'),
+                Class(CharClass::from_range('A', 'Z').ignore_case())
+            ]
+        );
+        assert_eq!(
+            pattern.with_case_folding(CaseFolding::Simple).elements(),
+            &[
+                Char('a'),
+                Char('ß'),
+                Class(CharClass::from_range('A', 'Z').ignore_case())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Pattern::parse(r"a\"), Err(PatternError::TrailingEscape));
+        assert_eq!(Pattern::parse("a*b"), Err(PatternError::UnboundedGap(1)));
+        assert_eq!(Pattern::parse("a*{3,1}b"), Err(PatternError::InvalidGap(1)));
+        assert_eq!(Pattern::parse("a*{x}b"), Err(PatternError::InvalidGap(1)));
+        assert_eq!(Pattern::parse("a*{1,2"), Err(PatternError::InvalidGap(1)));
+        assert_eq!(Pattern::parse("*{1}a"), Err(PatternError::GapAtBoundary));
+        assert_eq!(Pattern::parse("a*{1}"), Err(PatternError::GapAtBoundary));
+        assert_eq!(
+            Pattern::parse("a[bc"),
+            Err(PatternError::UnterminatedClass(1))
+        );
+        assert_eq!(
+            Pattern::parse(r"a\p{Xx}"),
+            Err(PatternError::InvalidClass(2))
+        );
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    fuzzy_search_options::{FuzzySearchOptions, Prefilter},
+    pattern::PatternElement,
+};
+
+/// Sorted, non overlapping ranges of text indexes, queried in increasing order
+pub(crate) struct Windows {
+    ranges: Vec<Range<usize>>,
+    current: usize,
+}
+
+impl Windows {
+    /// Merges overlapping and adjacent ranges
+    pub fn new(mut ranges: Vec<Range<usize>>) -> Self {
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+
+        for range in ranges.into_iter().filter(|r| !r.is_empty()) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        Self {
+            ranges: merged,
+            current: 0,
+        }
+    }
+
+    /// Extends each window at the end, eg to cover the ends of matches starting in it
+    pub fn extended(&self, length: usize) -> Self {
+        Self::new(
+            self.ranges
+                .iter()
+                .map(|r| r.start..r.end + length)
+                .collect(),
+        )
+    }
+
+    /// The parts of the windows inside the range
+    pub fn restricted(&self, range: Range<usize>) -> Self {
+        Self::new(
+            self.ranges
+                .iter()
+                .map(|r| r.start.max(range.start)..r.end.min(range.end))
+                .collect(),
+        )
+    }
+
+    /// The indexes inside both windows
+    pub fn intersected(&self, other: &Windows) -> Self {
+        let (mut i, mut j) = (0, 0);
+        let mut ranges = Vec::new();
+
+        while let (Some(a), Some(b)) = (self.ranges.get(i), other.ranges.get(j)) {
+            ranges.push(a.start.max(b.start)..a.end.min(b.end));
+
+            match a.end <= b.end {
+                true => i += 1,
+                false => j += 1,
+            }
+        }
+
+        Self::new(ranges)
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// Smallest index at or after the given index which is inside a window, None if there are no more windows
+    #[inline(always)]
+    pub fn next_index(&mut self, index: usize) -> Option<usize> {
+        while let Some(range) = self.ranges.get(self.current) {
+            if index < range.end {
+                return Some(index.max(range.start));
+            }
+
+            self.current += 1;
+        }
+
+        None
+    }
+
+    /// The rest of the window containing the index or the next window after it, None if there are no more windows
+    pub fn next_range(&mut self, index: usize) -> Option<Range<usize>> {
+        let start = self.next_index(index)?;
+        Some(start..self.ranges[self.current].end)
+    }
+}
+
+/// Next index to scan, the index itself if there are no windows, None if there is nothing left to scan
+#[inline(always)]
+pub(crate) fn next_window_index(windows: &mut Option<Windows>, index: usize) -> Option<usize> {
+    match windows {
+        Some(windows) => windows.next_index(index),
+        None => Some(index),
+    }
+}
+
+/// Next range of indexes before the length to scan, all of them if there are no windows
+pub(crate) fn next_window_range(
+    windows: &mut Option<Windows>,
+    index: usize,
+    length: usize,
+) -> Option<Range<usize>> {
+    let range = match windows {
+        Some(windows) => windows.next_range(index)?,
+        None => index..length,
+    };
+
+    Some(range.start..range.end.min(length)).filter(|r| !r.is_empty())
+}
+
+/// Windows of possible match start indexes found by the prefilter in the options.
+/// None if there is no prefilter, or if it cannot be used with the pattern and options
+pub(crate) fn start_windows(
+    pattern: &[PatternElement],
+    text: &[char],
+    weighted: bool,
+    options: &FuzzySearchOptions,
+) -> Option<Windows> {
+    match options.prefilter {
+        Prefilter::None => None,
+        Prefilter::ExactSeeds => {
+            exact_seed_windows(&literal(pattern, weighted, options)?, text, options)
+        }
+        Prefilter::QGrams { q } => {
+            qgram_windows(&literal(pattern, weighted, options)?, text, q, options)
+        }
+    }
+}
+
+/// The pattern chars, if edits are the only way a match can differ from the pattern and each edit has full cost
+fn literal(
+    pattern: &[PatternElement],
+    weighted: bool,
+    options: &FuzzySearchOptions,
+) -> Option<Vec<char>> {
+    if weighted
+        || options.char_matcher.is_some()
+        || options.layout_tolerant
+        || options.ignorable_chars.is_some()
+        || options.is_anchored_start()
+        || options.is_anchored_end()
+    {
+        return None;
+    }
+
+    pattern
+        .iter()
+        .map(|e| match e {
+            PatternElement::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splits the pattern into max_total_distance + 1 pieces. Each edit can break at most one piece, so every match contains one of them exactly.
+/// A piece at pattern offset p found at text index t puts the match start within the insertion and deletion limits of t - p
+fn exact_seed_windows(
+    pattern: &[char],
+    text: &[char],
+    options: &FuzzySearchOptions,
+) -> Option<Windows> {
+    let seeds = seeds(pattern, options)?;
+
+    Some(Windows::new(
+        find_seeds(&seeds, text)
+            .into_iter()
+            .map(|(text_index, seed)| seed_window(text_index, seeds[seed].0, options))
+            .collect(),
+    ))
+}
+
+/// Exact seed windows for many patterns, found with a single scan of the text for the seeds of all of them.
+/// None for the patterns the seeds cannot be used with, or whose seeds are too short to be shared without slowing down the scan for the others
+pub(crate) fn shared_seed_windows(
+    patterns: &[(&[PatternElement], &FuzzySearchOptions)],
+    text: &[char],
+) -> Vec<Option<Windows>> {
+    let literals = patterns
+        .iter()
+        .map(|(pattern, options)| literal(pattern, false, options))
+        .collect::<Vec<_>>();
+
+    // seeds of all patterns, with the index of the pattern they belong to
+    let mut owners = Vec::new();
+    let mut seeds = Vec::new();
+    let mut ranges: Vec<Option<Vec<Range<usize>>>> = vec![None; patterns.len()];
+
+    for (i, (literal, (_, options))) in literals.iter().zip(patterns).enumerate() {
+        let pattern_seeds = literal.as_deref().and_then(|l| self::seeds(l, options));
+
+        if let Some(pattern_seeds) = pattern_seeds.filter(|s| {
+            s.iter()
+                .all(|(_, seed)| seed.len() >= SHARED_SEED_MIN_LENGTH)
+        }) {
+            owners.extend(std::iter::repeat_n(i, pattern_seeds.len()));
+            seeds.extend(pattern_seeds);
+            ranges[i] = Some(Vec::new());
+        }
+    }
+
+    for (text_index, seed) in find_seeds(&seeds, text) {
+        let owner = owners[seed];
+
+        if let Some(ranges) = &mut ranges[owner] {
+            ranges.push(seed_window(text_index, seeds[seed].0, patterns[owner].1));
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|ranges| ranges.map(Windows::new))
+        .collect()
+}
+
+// every text position is hashed with the length of the shortest seed, shorter seeds would match almost everywhere
+const SHARED_SEED_MIN_LENGTH: usize = 3;
+
+/// The max_total_distance + 1 pattern pieces with their pattern offsets, None if there are more pieces than pattern chars
+fn seeds<'p>(
+    pattern: &'p [char],
+    options: &FuzzySearchOptions,
+) -> Option<Vec<(usize, &'p [char])>> {
+    let pieces = options.max_total_distance + 1;
+
+    if pieces > pattern.len() {
+        return None;
+    }
+
+    Some(
+        (0..pieces)
+            .map(|i| {
+                let start = i * pattern.len() / pieces;
+                (start, &pattern[start..(i + 1) * pattern.len() / pieces])
+            })
+            .collect(),
+    )
+}
+
+/// Possible match starts for a seed at pattern offset found at the text index
+fn seed_window(text_index: usize, offset: usize, options: &FuzzySearchOptions) -> Range<usize> {
+    let before = options.max_insertions.min(options.max_total_distance);
+    let after = options.max_deletions.min(options.max_total_distance);
+
+    (text_index.saturating_sub(offset + before))..(text_index + after + 1).saturating_sub(offset)
+}
+
+/// Minimum number of pattern q-grams in any text a distance of at most max_distance from the pattern.
+/// Each edit destroys at most q of the m - q + 1 pattern q-grams
+fn qgram_threshold(pattern_length: usize, max_distance: usize, q: usize) -> usize {
+    (pattern_length + 1).saturating_sub(q * (max_distance + 1))
+}
+
+/// The longest q which still gives a positive threshold, but no longer than needed for a q-gram to be rare in the text
+fn auto_q(pattern_length: usize, max_distance: usize, text: &[char]) -> usize {
+    let alphabet_size = text.iter().collect::<HashSet<_>>().len().max(2);
+    let rare = (text.len().max(2) as f64).log(alphabet_size as f64).ceil() as usize;
+
+    (1..=rare.max(1))
+        .take_while(|q| qgram_threshold(pattern_length, max_distance, *q) > 0)
+        .last()
+        .unwrap_or(1)
+}
+
+/// Slides a window of the longest possible match length over the text and counts the q-grams it shares with the pattern,
+/// each pattern q-gram counted at most as many times as it occurs in the pattern.
+/// A match can only start where the window starting at the same index reaches the threshold
+fn qgram_windows(
+    pattern: &[char],
+    text: &[char],
+    q: Option<usize>,
+    options: &FuzzySearchOptions,
+) -> Option<Windows> {
+    let max_distance = options.max_total_distance;
+    let q = q.unwrap_or_else(|| auto_q(pattern.len(), max_distance, text));
+    let threshold = qgram_threshold(pattern.len(), max_distance, q);
+
+    if q == 0 || threshold == 0 {
+        return None;
+    }
+
+    // index of each distinct pattern q-gram by hash, and how many times it occurs in the pattern
+    let mut pattern_qgrams: HashMap<u64, usize> = HashMap::new();
+    let mut limits = Vec::new();
+
+    for qgram in rolling_hashes(pattern, q) {
+        let id = *pattern_qgrams.entry(qgram).or_insert_with(|| {
+            limits.push(0);
+            limits.len() - 1
+        });
+        limits[id] += 1;
+    }
+
+    let text_qgrams = rolling_hashes(text, q)
+        .map(|qgram| pattern_qgrams.get(&qgram).copied())
+        .collect::<Vec<_>>();
+
+    let window_qgrams = pattern.len() + options.max_insertions.min(max_distance) - q + 1;
+    let mut counts = vec![0; limits.len()];
+    let mut shared = 0;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for id in text_qgrams.iter().take(window_qgrams).flatten() {
+        shared += (counts[*id] < limits[*id]) as usize;
+        counts[*id] += 1;
+    }
+
+    for start in 0..text_qgrams.len() {
+        if start > 0 {
+            if let Some(id) = text_qgrams[start - 1] {
+                counts[id] -= 1;
+                shared -= (counts[id] < limits[id]) as usize;
+            }
+
+            if let Some(Some(id)) = text_qgrams.get(start + window_qgrams - 1) {
+                shared += (counts[*id] < limits[*id]) as usize;
+                counts[*id] += 1;
+            }
+        }
+
+        if shared >= threshold {
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end += 1,
+                _ => ranges.push(start..start + 1),
+            }
+        }
+    }
+
+    Some(Windows::new(ranges))
+}
+
+/// Polynomial hashes of all substrings of the given length, colliding substrings only make the filters less selective
+fn rolling_hashes(chars: &[char], length: usize) -> impl Iterator<Item = u64> + '_ {
+    const BASE: u64 = 0x100000001b3;
+
+    // weight of the char leaving the window
+    let leaving = BASE.wrapping_pow(length.saturating_sub(1) as u32);
+    let mut hash = chars
+        .iter()
+        .take(length)
+        .fold(0u64, |h, c| h.wrapping_mul(BASE).wrapping_add(*c as u64));
+
+    (0..(chars.len() + 1).saturating_sub(length)).map(move |i| {
+        if i > 0 {
+            hash = hash
+                .wrapping_sub((chars[i - 1] as u64).wrapping_mul(leaving))
+                .wrapping_mul(BASE)
+                .wrapping_add(chars[i + length - 1] as u64);
+        }
+
+        hash
+    })
+}
+
+/// Text indexes and seed indexes of exact seed occurrences, found with a rolling hash over the length of the shortest seed
+fn find_seeds(seeds: &[(usize, &[char])], text: &[char]) -> Vec<(usize, usize)> {
+    let length = seeds.iter().map(|(_, s)| s.len()).min().unwrap_or(0);
+
+    if length == 0 {
+        return Vec::new();
+    }
+
+    let mut seeds_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, (_, seed)) in seeds.iter().enumerate() {
+        let hash = rolling_hashes(&seed[..length], length).next().unwrap_or(0);
+        seeds_by_hash.entry(hash).or_default().push(i);
+    }
+
+    let mut hits = Vec::new();
+
+    for (i, hash) in rolling_hashes(text, length).enumerate() {
+        if let Some(candidates) = seeds_by_hash.get(&hash) {
+            for &seed in candidates {
+                if text[i..].starts_with(seeds[seed].1) {
+                    hits.push((i, seed));
+                }
+            }
+        }
+    }
+
+    hits
+}
+
+#[cfg(test)]
+mod prefilter_tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_windows() {
+        let mut windows = Windows::new(vec![10..12, 2..4, 3..6, 6..7, 20..20]);
+
+        assert_eq!(vec![2..7, 10..12], windows.ranges);
+        assert_eq!(Some(2), windows.next_index(0));
+        assert_eq!(Some(6), windows.next_index(6));
+        assert_eq!(Some(10), windows.next_index(7));
+        assert_eq!(Some(11), windows.next_index(11));
+        assert_eq!(None, windows.next_index(12));
+    }
+
+    #[test]
+    fn test_windows_extended() {
+        let windows = Windows::new(vec![2..4, 8..9]).extended(4);
+
+        assert_eq!(vec![2..13], windows.ranges);
+    }
+
+    #[test]
+    fn test_windows_restricted() {
+        let windows = Windows::new(vec![2..4, 8..12, 20..30]).restricted(3..21);
+
+        assert_eq!(vec![3..4, 8..12, 20..21], windows.ranges);
+    }
+
+    #[test]
+    fn test_windows_intersected() {
+        let windows = Windows::new(vec![2..4, 8..12, 20..30]);
+        let other = Windows::new(vec![0..3, 9..10, 11..25]);
+
+        assert_eq!(
+            vec![2..3, 9..10, 11..12, 20..25],
+            windows.intersected(&other).ranges
+        );
+    }
+
+    #[test]
+    fn test_find_seeds() {
+        let pattern = chars("abcdefg");
+        let seeds = [(0, &pattern[..3]), (3, &pattern[3..])];
+        let text = chars("xxabcxxdefgxxdefxabc");
+
+        assert_eq!(vec![(2, 0), (7, 1), (17, 0)], find_seeds(&seeds, &text));
+    }
+
+    #[test]
+    fn test_exact_seed_windows() {
+        let options = FuzzySearchOptions::new(1);
+        let pattern = chars("pattern");
+        let text = chars("------------patxern------------");
+
+        let windows = exact_seed_windows(&pattern, &text, &options).unwrap();
+
+        // "pat" at 12 and "xern" does not occur
+        assert_eq!(vec![11..14], windows.ranges);
+    }
+
+    #[test]
+    fn test_shared_seed_windows() {
+        let text = chars("------------patxern------------ constitution --- counstitution --");
+        let elements = |s: &str| s.chars().map(PatternElement::Char).collect::<Vec<_>>();
+
+        let patterns = [
+            (elements("pattern"), FuzzySearchOptions::new(1)),
+            (elements("constitution"), FuzzySearchOptions::new(2)),
+            (elements("pattern"), FuzzySearchOptions::new(2)), // seeds of 2 chars are not shared
+            (vec![PatternElement::Any; 8], FuzzySearchOptions::new(1)),
+        ];
+
+        let windows = shared_seed_windows(
+            &patterns
+                .iter()
+                .map(|(p, o)| (&p[..], o))
+                .collect::<Vec<_>>(),
+            &text,
+        );
+
+        for i in 0..2 {
+            let pattern = literal(&patterns[i].0, false, &patterns[i].1).unwrap();
+            let expected = exact_seed_windows(&pattern, &text, &patterns[i].1).unwrap();
+
+            assert_eq!(expected.ranges, windows[i].as_ref().unwrap().ranges);
+        }
+
+        assert_eq!(vec![11..14], windows[0].as_ref().unwrap().ranges);
+        assert!(windows[2].is_none());
+        assert!(windows[3].is_none());
+    }
+
+    #[test]
+    fn test_not_applicable() {
+        let text = chars("pattern");
+        let pattern = [PatternElement::Char('a'), PatternElement::Any];
+        let mut options = FuzzySearchOptions::new(1);
+        options.prefilter = Prefilter::ExactSeeds;
+
+        assert!(start_windows(&pattern, &text, false, &options).is_none());
+
+        let pattern = chars("ab")
+            .into_iter()
+            .map(PatternElement::Char)
+            .collect::<Vec<_>>();
+
+        assert!(start_windows(&pattern, &text, false, &options).is_some());
+        assert!(start_windows(&pattern, &text, true, &options).is_none());
+
+        options.max_total_distance = 2;
+        assert!(start_windows(&pattern, &text, false, &options).is_none());
+    }
+
+    #[test]
+    fn test_qgram_threshold() {
+        assert_eq!(6, qgram_threshold(7, 0, 2));
+        assert_eq!(2, qgram_threshold(7, 2, 2));
+        assert_eq!(0, qgram_threshold(7, 3, 2));
+    }
+
+    #[test]
+    fn test_auto_q() {
+        let dna = chars(&"acgtgcatgcaatcgatcgtacg".repeat(1000));
+        let text = chars(&"the quick brown fox jumps over the lazy dog ".repeat(500));
+
+        assert_eq!(8, auto_q(38, 3, &dna));
+        assert_eq!(3, auto_q(14, 3, &text));
+        assert_eq!(1, auto_q(14, 13, &text));
+    }
+
+    #[test]
+    fn test_qgram_windows() {
+        let options = FuzzySearchOptions::new(1);
+        let pattern = chars("pattern");
+        let text = chars("----------patxern----------xxxxxxxxxxxxx");
+
+        let windows = qgram_windows(&pattern, &text, Some(2), &options).unwrap();
+
+        // 4 of the 6 pattern bigrams are needed, "pa" "at" "er" and "rn" are shared with the match
+        assert_eq!(vec![9..11], windows.ranges);
+        assert!(windows.ranges[0].contains(&10));
+    }
+
+    #[test]
+    fn test_qgram_windows_multiplicity() {
+        let options = FuzzySearchOptions::new(0);
+        let pattern = chars("abab");
+
+        // "ab" occurs twice in the pattern, a text with it three times but no "ba" does not reach the threshold of 3
+        let windows = qgram_windows(&pattern, &chars("abxabab"), Some(2), &options).unwrap();
+        assert_eq!(vec![3..4], windows.ranges);
+
+        let windows = qgram_windows(&pattern, &chars("ababxab"), Some(2), &options).unwrap();
+        assert_eq!(vec![0..1], windows.ranges);
+    }
+}
@@ -0,0 +1,61 @@
+/// Instruction set used by the simd kernels, picked at runtime so a single binary runs well on different cpus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    /// No simd, one position at a time
+    Scalar,
+    /// Whatever the compile target supports, used on architectures without runtime detection
+    Portable,
+    /// 128 bit registers on x86_64
+    Sse42,
+    /// 256 bit registers on x86_64
+    Avx2,
+    /// 512 bit registers on x86_64
+    Avx512,
+}
+
+impl SimdLevel {
+    /// Best level supported by the cpu
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+                return SimdLevel::Avx512;
+            } else if is_x86_feature_detected!("avx2") {
+                return SimdLevel::Avx2;
+            } else if is_x86_feature_detected!("sse4.2") {
+                return SimdLevel::Sse42;
+            }
+        }
+
+        SimdLevel::Portable
+    }
+
+    /// Returns true if kernels for this level can run on the cpu
+    pub fn is_supported(self) -> bool {
+        match self {
+            SimdLevel::Scalar | SimdLevel::Portable => true,
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Sse42 => is_x86_feature_detected!("sse4.2"),
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx512 => {
+                is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw")
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod simd_level_tests {
+    use super::*;
+
+    #[test]
+    fn test_detected_level_is_supported() {
+        assert!(SimdLevel::detect().is_supported());
+        assert!(SimdLevel::Scalar.is_supported());
+        assert!(SimdLevel::Portable.is_supported());
+    }
+}